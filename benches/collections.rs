@@ -0,0 +1,25 @@
+use bsharp_lang::eval_str;
+use criterion::{criterion_group, criterion_main, Criterion};
+
+// Pushing onto a heap nobody else holds changes it in place; keeping a copy
+// alive makes every push clone the shared heap first.
+fn heap_push(c: &mut Criterion) {
+  let unshared = "Dim h = Heap()\nFor i = 1 To 2000\nHeapPush(h, i)\nNext";
+  let shared = "Dim h = Heap()\nFor i = 1 To 2000\nDim copy = h\nHeapPush(h, i)\nNext";
+  let mut group = c.benchmark_group("heap_push_2000");
+  group.bench_function("unshared", |b| b.iter(|| eval_str(unshared).unwrap()));
+  group.bench_function("shared", |b| b.iter(|| eval_str(shared).unwrap()));
+  group.finish();
+}
+
+fn set_add(c: &mut Criterion) {
+  let unshared = "Dim s = Set()\nFor i = 1 To 2000\nSetAdd(s, i)\nNext";
+  let shared = "Dim s = Set()\nFor i = 1 To 2000\nDim copy = s\nSetAdd(s, i)\nNext";
+  let mut group = c.benchmark_group("set_add_2000");
+  group.bench_function("unshared", |b| b.iter(|| eval_str(unshared).unwrap()));
+  group.bench_function("shared", |b| b.iter(|| eval_str(shared).unwrap()));
+  group.finish();
+}
+
+criterion_group!(benches, heap_push, set_add);
+criterion_main!(benches);
@@ -0,0 +1,33 @@
+use bsharp_lang::input_stream::InputStream;
+use bsharp_lang::lexer::Lexer;
+use bsharp_lang::parser::parse_source;
+use bsharp_lang::token_kind::TokenKind;
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+
+fn large_source() -> String {
+  // The sample has no trailing newline.
+  format!("{}\n", include_str!("../samples/test.bs")).repeat(1000)
+}
+
+fn lex_all(source: &str) -> usize {
+  let mut lexer = Lexer::new(InputStream::new(source, String::from("<bench>")));
+  let mut count = 0;
+  while lexer.next_token().unwrap().kind != TokenKind::EOF {
+    count += 1;
+  }
+  count
+}
+
+fn lexer(c: &mut Criterion) {
+  let source = large_source();
+  let mut group = c.benchmark_group("samples_x1000");
+  group.throughput(Throughput::Bytes(source.len() as u64));
+  group.bench_function("lex", |b| b.iter(|| lex_all(&source)));
+  group.bench_function("parse", |b| {
+    b.iter(|| parse_source(&source, "<bench>").unwrap())
+  });
+  group.finish();
+}
+
+criterion_group!(benches, lexer);
+criterion_main!(benches);
@@ -0,0 +1,28 @@
+use bsharp_lang::executor::Executor;
+use bsharp_lang::parser::parse_source;
+use criterion::{criterion_group, criterion_main, Criterion};
+use std::fs::File;
+
+// Every unbuffered Print is its own write to the file; buffered output is
+// written once when the program finishes.
+fn print_to_file(c: &mut Criterion) {
+  let program = parse_source("For i = 1 To 10000\nPrint(i)\nNext", "<bench>").unwrap();
+  let path = std::env::temp_dir().join("bsharp-bench-output.txt");
+  let mut group = c.benchmark_group("print_10000");
+  for buffered in [false, true] {
+    let name = if buffered { "buffered" } else { "unbuffered" };
+    group.bench_function(name, |b| {
+      b.iter(|| {
+        let mut executor = Executor::new();
+        executor.set_output(Box::new(File::create(&path).unwrap()));
+        executor.set_buffered(buffered);
+        executor.execute(&program).unwrap()
+      })
+    });
+  }
+  group.finish();
+  let _ = std::fs::remove_file(path);
+}
+
+criterion_group!(benches, print_to_file);
+criterion_main!(benches);
@@ -0,0 +1,18 @@
+use bsharp_lang::parser::parse_source;
+use criterion::{criterion_group, criterion_main, Criterion};
+
+// Left-associative chains are parsed in a loop, so 10000 terms neither recurse
+// 10000 deep nor trip the nesting limit.
+fn chain(c: &mut Criterion) {
+  let sum = format!("Dim x = 1{}", " + 1".repeat(9999));
+  let mixed = format!("Dim x = 1{}", " + 2 * 3 - 4".repeat(3333));
+  let mut group = c.benchmark_group("parse_chain_10000");
+  group.bench_function("sum", |b| b.iter(|| parse_source(&sum, "<bench>").unwrap()));
+  group.bench_function("mixed", |b| {
+    b.iter(|| parse_source(&mixed, "<bench>").unwrap())
+  });
+  group.finish();
+}
+
+criterion_group!(benches, chain);
+criterion_main!(benches);
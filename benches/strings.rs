@@ -0,0 +1,17 @@
+use bsharp_lang::eval_str;
+use criterion::{criterion_group, criterion_main, Criterion};
+
+// `s = s + "x"` copies the whole string on every pass; Append grows one
+// buffer in place.
+fn append_10000(c: &mut Criterion) {
+  let naive = "Dim s = \"\"\nFor i = 1 To 10000\ns = s + \"x\"\nNext";
+  let builder =
+    "Dim b = StringBuilder()\nFor i = 1 To 10000\nAppend(b, \"x\")\nNext\nDim s = Build(b)";
+  let mut group = c.benchmark_group("append_10000");
+  group.bench_function("concatenation", |b| b.iter(|| eval_str(naive).unwrap()));
+  group.bench_function("string_builder", |b| b.iter(|| eval_str(builder).unwrap()));
+  group.finish();
+}
+
+criterion_group!(benches, append_10000);
+criterion_main!(benches);
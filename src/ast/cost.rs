@@ -0,0 +1,80 @@
+use super::expression::{left_spine, Expression};
+
+const LITERAL_COST: u64 = 1;
+const OPERATOR_COST: u64 = 2;
+const METHOD_COST: u64 = 20;
+
+// A rough, static guess of how much work evaluating `expression` takes, for
+// hosts that schedule or throttle scripts. Only relative sizes are meaningful.
+pub fn estimate_cost(expression: &Expression) -> u64 {
+  match expression {
+    Expression::Identifier(_)
+    | Expression::String(_)
+    | Expression::Integer(_)
+    | Expression::Float(_)
+    | Expression::Boolean(_) => LITERAL_COST,
+    Expression::Unary { expression, .. } => OPERATOR_COST.saturating_add(estimate_cost(expression)),
+    // Walked along the left spine so a long chain does not recurse per operator.
+    Expression::Binary {
+      left,
+      operator,
+      right,
+    } => {
+      let (first, rest) = left_spine(left, operator, right);
+      rest.iter().fold(estimate_cost(first), |total, (_, right)| {
+        total
+          .saturating_add(OPERATOR_COST)
+          .saturating_add(estimate_cost(right))
+      })
+    }
+    Expression::MethodInvocation { arguments, .. } => {
+      METHOD_COST.saturating_add(total_cost(arguments))
+    }
+    Expression::Sequence(expressions) => total_cost(expressions),
+    Expression::Array(elements) => LITERAL_COST.saturating_add(total_cost(elements)),
+    Expression::Spread(expression) => OPERATOR_COST.saturating_add(estimate_cost(expression)),
+    Expression::Index { expression, index } => OPERATOR_COST
+      .saturating_add(estimate_cost(expression))
+      .saturating_add(estimate_cost(index)),
+  }
+}
+
+fn total_cost(expressions: &[Expression]) -> u64 {
+  expressions
+    .iter()
+    .fold(0, |total, e| total.saturating_add(estimate_cost(e)))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::ast::statement::Statement;
+  use crate::parser::parse_source;
+
+  fn cost(source: &str) -> u64 {
+    let program = parse_source(&format!("Dim x = {}", source), "<test>").unwrap();
+    match &program.statements[0] {
+      Statement::Declaration { expression, .. } => estimate_cost(expression),
+      s => panic!("expected a declaration, got {:?}", s),
+    }
+  }
+
+  #[test]
+  fn nesting_costs_more_than_a_literal() {
+    assert_eq!(cost("1"), LITERAL_COST);
+    assert!(cost("(1 + 2) * (3 - 4)") > cost("1 + 2"));
+    assert!(cost("1 + 2") > cost("1"));
+  }
+
+  #[test]
+  fn a_method_call_costs_more_than_arithmetic() {
+    assert!(cost("Len(\"a\")") > cost("1 + 2 * 3 - 4"));
+    assert!(cost("Len(\"a\" + \"b\")") > cost("Len(\"a\")"));
+  }
+
+  #[test]
+  fn a_long_chain_is_costed() {
+    let source = format!("1{}", " + 1".repeat(9999));
+    assert_eq!(cost(&source), 10000 * LITERAL_COST + 9999 * OPERATOR_COST);
+  }
+}
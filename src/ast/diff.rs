@@ -0,0 +1,207 @@
+use super::program::Program;
+use super::statement::Statement;
+
+// Differences between two programs' top-level statements. Removed indices
+// count the statements of the first program; Added and Changed ones count
+// those of the second.
+#[derive(Debug, PartialEq, Clone)]
+pub enum AstChange {
+  Added {
+    index: usize,
+    statement: Statement,
+  },
+  Removed {
+    index: usize,
+    statement: Statement,
+  },
+  Changed {
+    index: usize,
+    before: Statement,
+    after: Statement,
+  },
+}
+
+// Statements are aligned by their longest common subsequence, so inserting or
+// deleting one is reported as a single Added or Removed. Between two aligned
+// statements, removed and added ones are paired up as Changed.
+pub fn ast_diff(a: &Program, b: &Program) -> Vec<AstChange> {
+  let (a, b) = (&a.statements, &b.statements);
+  let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+  let suffix = a[prefix..]
+    .iter()
+    .rev()
+    .zip(b[prefix..].iter().rev())
+    .take_while(|(x, y)| x == y)
+    .count();
+  let (a_middle, b_middle) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+  let mut changes = vec![];
+  let (mut i, mut j) = (prefix, prefix);
+  for (next_i, next_j) in common_subsequence(a_middle, b_middle)
+    .into_iter()
+    .map(|(x, y)| (prefix + x, prefix + y))
+    .chain(std::iter::once((a.len() - suffix, b.len() - suffix)))
+  {
+    while i < next_i && j < next_j {
+      changes.push(AstChange::Changed {
+        index: j,
+        before: a[i].clone(),
+        after: b[j].clone(),
+      });
+      i += 1;
+      j += 1;
+    }
+    for (index, statement) in a.iter().enumerate().take(next_i).skip(i) {
+      changes.push(AstChange::Removed {
+        index,
+        statement: statement.clone(),
+      });
+    }
+    for (index, statement) in b.iter().enumerate().take(next_j).skip(j) {
+      changes.push(AstChange::Added {
+        index,
+        statement: statement.clone(),
+      });
+    }
+    i = next_i + 1;
+    j = next_j + 1;
+  }
+  changes
+}
+
+// The index pairs of a longest common subsequence of `a` and `b`, in order.
+fn common_subsequence(a: &[Statement], b: &[Statement]) -> Vec<(usize, usize)> {
+  // lengths[i][j] is the length of the longest common subsequence of a[i..]
+  // and b[j..].
+  let mut lengths = vec![vec![0; b.len() + 1]; a.len() + 1];
+  for i in (0..a.len()).rev() {
+    for j in (0..b.len()).rev() {
+      lengths[i][j] = if a[i] == b[j] {
+        lengths[i + 1][j + 1] + 1
+      } else {
+        lengths[i + 1][j].max(lengths[i][j + 1])
+      };
+    }
+  }
+  let mut pairs = vec![];
+  let (mut i, mut j) = (0, 0);
+  while i < a.len() && j < b.len() {
+    if a[i] == b[j] {
+      pairs.push((i, j));
+      i += 1;
+      j += 1;
+    } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+      i += 1;
+    } else {
+      j += 1;
+    }
+  }
+  pairs
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::ast::expression::Expression;
+  use crate::parser::parse_source;
+
+  fn parse(source: &str) -> Program {
+    parse_source(source, "<test>").unwrap()
+  }
+
+  fn declaration(identifier: &str, value: i32) -> Statement {
+    Statement::Declaration {
+      identifier: identifier.to_string(),
+      expression: Expression::Integer(value),
+      constant: false,
+    }
+  }
+
+  #[test]
+  fn one_changed_literal_is_one_change() {
+    let a = parse("Dim x = 1\nDim y = 2\nDim z = 3");
+    let b = parse("Dim x = 1\nDim y = 20\nDim z = 3");
+    assert_eq!(
+      ast_diff(&a, &b),
+      vec![AstChange::Changed {
+        index: 1,
+        before: declaration("y", 2),
+        after: declaration("y", 20),
+      }]
+    );
+    assert_eq!(ast_diff(&a, &a), vec![]);
+  }
+
+  #[test]
+  fn trailing_statements_are_added_or_removed() {
+    let a = parse("Dim x = 1");
+    let b = parse("Dim x = 1\nDim y = 2");
+    assert_eq!(
+      ast_diff(&a, &b),
+      vec![AstChange::Added {
+        index: 1,
+        statement: declaration("y", 2),
+      }]
+    );
+    assert_eq!(
+      ast_diff(&b, &a),
+      vec![AstChange::Removed {
+        index: 1,
+        statement: declaration("y", 2),
+      }]
+    );
+  }
+
+  #[test]
+  fn a_change_in_a_long_chain_is_reported() {
+    let chain = format!("Dim x = 1{}", " + 1".repeat(9999));
+    let a = parse(&chain);
+    let b = parse(&chain.replacen("1 +", "2 +", 1));
+    let changes = ast_diff(&a, &b);
+    assert_eq!(changes.len(), 1);
+    assert!(matches!(changes[0], AstChange::Changed { index: 0, .. }));
+  }
+
+  #[test]
+  fn a_statement_inserted_at_the_top_is_one_addition() {
+    let a = parse("Dim x = 1\nDim y = 2\nDim z = 3");
+    let b = parse("Dim w = 0\nDim x = 1\nDim y = 2\nDim z = 3");
+    assert_eq!(
+      ast_diff(&a, &b),
+      vec![AstChange::Added {
+        index: 0,
+        statement: declaration("w", 0),
+      }]
+    );
+    assert_eq!(
+      ast_diff(&b, &a),
+      vec![AstChange::Removed {
+        index: 0,
+        statement: declaration("w", 0),
+      }]
+    );
+  }
+
+  #[test]
+  fn changes_between_aligned_statements_are_paired() {
+    let a = parse("Dim a = 1\nDim b = 2\nDim c = 3\nDim d = 4\nDim e = 5");
+    let b = parse("Dim a = 1\nDim b = 20\nDim d = 4\nDim x = 0\nDim e = 5");
+    assert_eq!(
+      ast_diff(&a, &b),
+      vec![
+        AstChange::Changed {
+          index: 1,
+          before: declaration("b", 2),
+          after: declaration("b", 20),
+        },
+        AstChange::Removed {
+          index: 2,
+          statement: declaration("c", 3),
+        },
+        AstChange::Added {
+          index: 3,
+          statement: declaration("x", 0),
+        },
+      ]
+    );
+  }
+}
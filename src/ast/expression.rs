@@ -1,20 +1,236 @@
 use super::{BinaryOperator, UnaryOperator};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 use std::fmt;
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, Serialize, Deserialize)]
 pub enum Expression {
   Identifier(String),
   String(String),
   Integer(i32),
+  Float(f64),
+  Boolean(bool),
   Unary {
     operator: UnaryOperator,
     expression: Box<Expression>,
   },
+  #[serde(
+    serialize_with = "serialize_chain",
+    deserialize_with = "deserialize_chain"
+  )]
   Binary {
     left: Box<Expression>,
     operator: BinaryOperator,
     right: Box<Expression>,
   },
+  MethodInvocation {
+    identifier: String,
+    arguments: Vec<Expression>,
+  },
+  Sequence(Vec<Expression>),
+  Array(Vec<Expression>),
+  // `...array`, only inside array literals and argument lists.
+  Spread(Box<Expression>),
+  Index {
+    expression: Box<Expression>,
+    index: Box<Expression>,
+  },
+}
+
+// The operands down the left of a chain such as `1 + 2 + 3`, innermost first:
+// (1, [(+, 2), (+, 3)]). Walking it is a loop, however long the chain is.
+pub(crate) fn left_spine<'a>(
+  left: &'a Expression,
+  operator: &'a BinaryOperator,
+  right: &'a Expression,
+) -> (&'a Expression, Vec<(&'a BinaryOperator, &'a Expression)>) {
+  let mut rest = vec![(operator, right)];
+  let mut first = left;
+  while let Expression::Binary {
+    left,
+    operator,
+    right,
+  } = first
+  {
+    rest.push((operator, right));
+    first = left;
+  }
+  rest.reverse();
+  (first, rest)
+}
+
+// Compared by hand so that a long chain is walked in a loop instead of
+// recursing once per operator.
+impl PartialEq for Expression {
+  fn eq(&self, other: &Expression) -> bool {
+    match (self, other) {
+      (
+        Expression::Binary {
+          left,
+          operator,
+          right,
+        },
+        Expression::Binary {
+          left: other_left,
+          operator: other_operator,
+          right: other_right,
+        },
+      ) => {
+        let (first, rest) = left_spine(left, operator, right);
+        let (other_first, other_rest) = left_spine(other_left, other_operator, other_right);
+        rest == other_rest && first == other_first
+      }
+      (Expression::Identifier(a), Expression::Identifier(b)) => a == b,
+      (Expression::String(a), Expression::String(b)) => a == b,
+      (Expression::Integer(a), Expression::Integer(b)) => a == b,
+      (Expression::Float(a), Expression::Float(b)) => a == b,
+      (Expression::Boolean(a), Expression::Boolean(b)) => a == b,
+      (
+        Expression::Unary {
+          operator,
+          expression,
+        },
+        Expression::Unary {
+          operator: other_operator,
+          expression: other_expression,
+        },
+      ) => operator == other_operator && expression == other_expression,
+      (
+        Expression::MethodInvocation {
+          identifier,
+          arguments,
+        },
+        Expression::MethodInvocation {
+          identifier: other_identifier,
+          arguments: other_arguments,
+        },
+      ) => identifier == other_identifier && arguments == other_arguments,
+      (Expression::Sequence(a), Expression::Sequence(b)) => a == b,
+      (Expression::Array(a), Expression::Array(b)) => a == b,
+      (Expression::Spread(a), Expression::Spread(b)) => a == b,
+      (
+        Expression::Index { expression, index },
+        Expression::Index {
+          expression: other_expression,
+          index: other_index,
+        },
+      ) => expression == other_expression && index == other_index,
+      _ => false,
+    }
+  }
+}
+
+// Cloned by hand for the same reason: the chain is rebuilt from its left
+// spine in a loop.
+impl Clone for Expression {
+  fn clone(&self) -> Expression {
+    match self {
+      Expression::Binary {
+        left,
+        operator,
+        right,
+      } => {
+        let (first, rest) = left_spine(left, operator, right);
+        rest
+          .into_iter()
+          .fold(first.clone(), |left, (operator, right)| {
+            Expression::Binary {
+              left: Box::new(left),
+              operator: operator.clone(),
+              right: Box::new(right.clone()),
+            }
+          })
+      }
+      Expression::Identifier(i) => Expression::Identifier(i.clone()),
+      Expression::String(s) => Expression::String(s.clone()),
+      Expression::Integer(n) => Expression::Integer(*n),
+      Expression::Float(n) => Expression::Float(*n),
+      Expression::Boolean(b) => Expression::Boolean(*b),
+      Expression::Unary {
+        operator,
+        expression,
+      } => Expression::Unary {
+        operator: operator.clone(),
+        expression: expression.clone(),
+      },
+      Expression::MethodInvocation {
+        identifier,
+        arguments,
+      } => Expression::MethodInvocation {
+        identifier: identifier.clone(),
+        arguments: arguments.clone(),
+      },
+      Expression::Sequence(expressions) => Expression::Sequence(expressions.clone()),
+      Expression::Array(elements) => Expression::Array(elements.clone()),
+      Expression::Spread(expression) => Expression::Spread(expression.clone()),
+      Expression::Index { expression, index } => Expression::Index {
+        expression: expression.clone(),
+        index: index.clone(),
+      },
+    }
+  }
+}
+
+// A Binary is written as its left spine, so a long chain is not nested once
+// per operator in the JSON.
+#[derive(Serialize)]
+struct ChainRef<'a> {
+  first: &'a Expression,
+  rest: Vec<(&'a BinaryOperator, &'a Expression)>,
+}
+
+#[derive(Deserialize)]
+struct Chain {
+  first: Expression,
+  rest: Vec<(BinaryOperator, Expression)>,
+}
+
+#[allow(clippy::borrowed_box)]
+fn serialize_chain<S: Serializer>(
+  left: &Box<Expression>,
+  operator: &BinaryOperator,
+  right: &Box<Expression>,
+  serializer: S,
+) -> Result<S::Ok, S::Error> {
+  let (first, rest) = left_spine(left, operator, right);
+  ChainRef { first, rest }.serialize(serializer)
+}
+
+type BinaryFields = (Box<Expression>, BinaryOperator, Box<Expression>);
+
+fn deserialize_chain<'de, D: Deserializer<'de>>(deserializer: D) -> Result<BinaryFields, D::Error> {
+  let Chain { first, rest } = Chain::deserialize(deserializer)?;
+  let mut rest = rest.into_iter();
+  let (operator, right) = rest
+    .next()
+    .ok_or_else(|| de::Error::invalid_length(0, &"at least one operator"))?;
+  let mut fields = (Box::new(first), operator, Box::new(right));
+  for (operator, right) in rest {
+    let (left, previous, last) = fields;
+    let left = Expression::Binary {
+      left,
+      operator: previous,
+      right: last,
+    };
+    fields = (Box::new(left), operator, Box::new(right));
+  }
+  Ok(fields)
+}
+
+// Writes a list with Display rather than Debug, which would recurse into
+// every operand of a chain.
+pub(crate) struct ExpressionList<'a>(pub &'a [Expression]);
+
+impl fmt::Display for ExpressionList<'_> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "[")?;
+    for (i, e) in self.0.iter().enumerate() {
+      if i > 0 {
+        write!(f, ", ")?;
+      }
+      write!(f, "{}", e)?;
+    }
+    write!(f, "]")
+  }
 }
 
 impl fmt::Display for Expression {
@@ -22,7 +238,9 @@ impl fmt::Display for Expression {
     match self {
       Expression::Identifier(i) => write!(f, "Expression::Identifier({})", i)?,
       Expression::Integer(n) => write!(f, "Expression::Intger({})", n)?,
+      Expression::Float(n) => write!(f, "Expression::Float({:?})", n)?,
       Expression::String(s) => write!(f, "Expression::String(\"{}\")", s)?,
+      Expression::Boolean(b) => write!(f, "Expression::Boolean({})", b)?,
       Expression::Unary {
         operator,
         expression,
@@ -31,7 +249,30 @@ impl fmt::Display for Expression {
         left,
         operator,
         right,
-      } => write!(f, "Expression::Binary ({} {} {})", left, operator, right)?,
+      } => {
+        let (first, rest) = left_spine(left, operator, right);
+        write!(f, "{}{}", "Expression::Binary (".repeat(rest.len()), first)?;
+        for (operator, right) in rest {
+          write!(f, " {} {})", operator, right)?;
+        }
+      }
+      Expression::MethodInvocation {
+        identifier,
+        arguments,
+      } => write!(
+        f,
+        "Expression::MethodInvocation ({}({}))",
+        identifier,
+        ExpressionList(arguments)
+      )?,
+      Expression::Sequence(expressions) => {
+        write!(f, "Expression::Sequence ({})", ExpressionList(expressions))?
+      }
+      Expression::Array(elements) => write!(f, "Expression::Array ({})", ExpressionList(elements))?,
+      Expression::Spread(expression) => write!(f, "Expression::Spread (...{})", expression)?,
+      Expression::Index { expression, index } => {
+        write!(f, "Expression::Index ({}[{}])", expression, index)?
+      }
     }
     Ok(())
   }
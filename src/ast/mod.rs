@@ -1,22 +1,43 @@
+use serde::{Deserialize, Serialize};
 use std::fmt;
 
+pub mod cost;
+pub mod diff;
 pub mod expression;
 pub mod program;
+pub mod source;
 pub mod statement;
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum UnaryOperator {
   NEGATIVE,
   POSITIVE,
   NOT,
 }
+impl UnaryOperator {
+  // Operands of `Not` take in comparisons, operands of a sign only `^`.
+  pub fn precedence(&self) -> u8 {
+    match self {
+      UnaryOperator::NOT => 4,
+      UnaryOperator::NEGATIVE | UnaryOperator::POSITIVE => 8,
+    }
+  }
+
+  pub fn spelling(&self) -> &'static str {
+    match self {
+      UnaryOperator::NEGATIVE => "-",
+      UnaryOperator::POSITIVE => "+",
+      UnaryOperator::NOT => "Not ",
+    }
+  }
+}
 impl fmt::Display for UnaryOperator {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
     write!(f, "{:?}", self)
   }
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum BinaryOperator {
   ADD,
   SUB,
@@ -34,6 +55,50 @@ pub enum BinaryOperator {
   LT,
   LE,
 }
+impl BinaryOperator {
+  // How tightly the operator binds; higher binds tighter.
+  pub fn precedence(&self) -> u8 {
+    match self {
+      BinaryOperator::XOR => 1,
+      BinaryOperator::OR => 2,
+      BinaryOperator::AND => 3,
+      BinaryOperator::EQ
+      | BinaryOperator::NE
+      | BinaryOperator::GT
+      | BinaryOperator::GE
+      | BinaryOperator::LT
+      | BinaryOperator::LE => 5,
+      BinaryOperator::ADD | BinaryOperator::SUB => 6,
+      BinaryOperator::MUL | BinaryOperator::DIV | BinaryOperator::MOD => 7,
+      BinaryOperator::EXPOTENTIAL => 9,
+    }
+  }
+
+  // `2 ^ 3 ^ 2` is `2 ^ (3 ^ 2)`; every other operator groups to the left.
+  pub fn is_right_associative(&self) -> bool {
+    *self == BinaryOperator::EXPOTENTIAL
+  }
+
+  pub fn spelling(&self) -> &'static str {
+    match self {
+      BinaryOperator::ADD => "+",
+      BinaryOperator::SUB => "-",
+      BinaryOperator::MUL => "*",
+      BinaryOperator::DIV => "/",
+      BinaryOperator::MOD => "Mod",
+      BinaryOperator::AND => "And",
+      BinaryOperator::OR => "Or",
+      BinaryOperator::XOR => "Xor",
+      BinaryOperator::EXPOTENTIAL => "^",
+      BinaryOperator::EQ => "==",
+      BinaryOperator::NE => "<>",
+      BinaryOperator::GT => ">",
+      BinaryOperator::GE => ">=",
+      BinaryOperator::LT => "<",
+      BinaryOperator::LE => "<=",
+    }
+  }
+}
 impl fmt::Display for BinaryOperator {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
     write!(f, "{:?}", self)
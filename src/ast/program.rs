@@ -1,7 +1,13 @@
 use super::statement::Statement;
+use serde::{de::Error, Deserialize, Serialize};
 use std::fmt;
 
-#[derive(Debug, PartialEq)]
+// How deeply objects and arrays may nest in the JSON of a Program, both ways.
+// serde_json's own limit of 128 is lower than what the parser accepts, so it
+// is replaced by this one, checked before anything is read.
+pub const MAX_JSON_DEPTH: usize = 512;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct Program {
   pub statements: Vec<Statement>,
 }
@@ -13,3 +19,108 @@ impl fmt::Display for Program {
     Ok(())
   }
 }
+
+pub fn program_to_json(program: &Program) -> Result<String, serde_json::Error> {
+  let json = serde_json::to_string(program)?;
+  check_json_depth(&json)?;
+  Ok(json)
+}
+
+pub fn program_from_json(json: &str) -> Result<Program, serde_json::Error> {
+  check_json_depth(json)?;
+  let mut deserializer = serde_json::Deserializer::from_str(json);
+  deserializer.disable_recursion_limit();
+  let program = Program::deserialize(&mut deserializer)?;
+  deserializer.end()?;
+  Ok(program)
+}
+
+fn check_json_depth(json: &str) -> Result<(), serde_json::Error> {
+  let mut depth = 0;
+  let mut in_string = false;
+  let mut escaped = false;
+  for b in json.bytes() {
+    if in_string {
+      match b {
+        _ if escaped => escaped = false,
+        b'\\' => escaped = true,
+        b'"' => in_string = false,
+        _ => {}
+      }
+      continue;
+    }
+    match b {
+      b'"' => in_string = true,
+      b'{' | b'[' => {
+        depth += 1;
+        if depth > MAX_JSON_DEPTH {
+          return Err(serde_json::Error::custom(format!(
+            "JSON nests more than {} levels deep",
+            MAX_JSON_DEPTH
+          )));
+        }
+      }
+      b'}' | b']' => depth -= 1,
+      _ => {}
+    }
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::parser::parse_source;
+
+  fn round_trip(source: &str) -> (Program, Program) {
+    let program = parse_source(source, "<test>").unwrap();
+    let json = program_to_json(&program).unwrap();
+    (program_from_json(&json).unwrap(), program)
+  }
+
+  #[test]
+  fn a_program_survives_a_round_trip() {
+    let source = "Const a = -1 + 2 * (3 - x) ^ 2\nDim b, c = [1, ...d], \"s\"\nIf Not a > 1.5 Then\n  Print(a[0], b)\nElse\n  For i = 1 To 3\n    Do\n      Exit Do\n    Loop\n  Next\nEnd If\n";
+    let (back, program) = round_trip(source);
+    assert_eq!(back, program);
+  }
+
+  #[test]
+  fn a_ten_thousand_term_chain_survives_a_round_trip() {
+    let source = format!("Dim x = 1{}", " + 2 * 3 - 4".repeat(3333));
+    let (back, program) = round_trip(&source);
+    assert_eq!(back, program);
+    assert_eq!(back.to_string(), program.to_string());
+  }
+
+  #[test]
+  fn the_deepest_nesting_the_parser_accepts_survives_a_round_trip() {
+    let depth = crate::parser::DEFAULT_MAX_NESTING_DEPTH - 1;
+    let calls = format!("Dim x = {}1{}", "f(".repeat(depth), ")".repeat(depth));
+    let (back, program) = round_trip(&calls);
+    assert_eq!(back, program);
+    let signs = format!("Dim x = {}y", "-".repeat(depth));
+    let (back, program) = round_trip(&signs);
+    assert_eq!(back, program);
+  }
+
+  #[test]
+  fn json_is_read_up_to_the_depth_limit() {
+    let json = |unary: usize| {
+      format!(
+        "{{\"statements\":[{{\"Declaration\":{{\"identifier\":\"x\",\"expression\":{}{{\"Boolean\":true}}{}}}}}]}}",
+        "{\"Unary\":{\"operator\":\"NOT\",\"expression\":".repeat(unary),
+        "}}".repeat(unary)
+      )
+    };
+    assert!(program_from_json(&json((MAX_JSON_DEPTH - 5) / 2)).is_ok());
+    let e = program_from_json(&json(MAX_JSON_DEPTH / 2)).unwrap_err();
+    assert!(e.to_string().contains("more than 512 levels"));
+  }
+
+  #[test]
+  fn a_chain_without_operators_is_rejected() {
+    let json = "{\"statements\":[{\"Assignment\":{\"identifier\":\"x\",\"expression\":{\"Binary\":{\"first\":{\"Integer\":1},\"rest\":[]}}}}]}";
+    assert!(program_from_json(json).is_err());
+  }
+}
@@ -0,0 +1,374 @@
+use super::expression::Expression;
+use super::program::Program;
+use super::statement::Statement;
+use super::{BinaryOperator, UnaryOperator};
+
+const INDENT: &str = "  ";
+
+// Renders `program` as B# source that parses back to the same tree. Comments
+// and redundant parentheses are not kept, and booleans use the default
+// `true`/`false` spellings.
+pub fn to_source(program: &Program) -> String {
+  let mut source = String::new();
+  write_statements(&mut source, &program.statements, 0);
+  source
+}
+
+pub fn expression_to_source(expression: &Expression) -> String {
+  let mut source = String::new();
+  write_expression(&mut source, expression, 0, 0);
+  source
+}
+
+fn write_statements(out: &mut String, statements: &[Statement], depth: usize) {
+  for s in statements {
+    write_statement(out, s, depth);
+  }
+}
+
+fn write_line(out: &mut String, depth: usize, line: &str) {
+  if !line.is_empty() {
+    out.push_str(&INDENT.repeat(depth));
+    out.push_str(line);
+  }
+  out.push('\n');
+}
+
+fn write_statement(out: &mut String, statement: &Statement, depth: usize) {
+  match statement {
+    Statement::Declaration {
+      identifier,
+      expression,
+      constant,
+    } => {
+      let line = format!(
+        "{} {} = {}",
+        declaration_keyword(*constant),
+        identifier,
+        expression_to_source(expression)
+      );
+      write_line(out, depth, &line);
+    }
+    Statement::Assignment {
+      identifier,
+      expression,
+    } => {
+      let line = format!("{} = {}", identifier, expression_to_source(expression));
+      write_line(out, depth, &line);
+    }
+    Statement::MultipleDeclaration {
+      identifiers,
+      expressions,
+      constant,
+    } => {
+      let line = format!(
+        "{} {} = {}",
+        declaration_keyword(*constant),
+        identifiers.join(", "),
+        list_to_source(expressions)
+      );
+      write_line(out, depth, &line);
+    }
+    Statement::MultipleAssignment {
+      identifiers,
+      expressions,
+    } => {
+      let line = format!(
+        "{} = {}",
+        identifiers.join(", "),
+        list_to_source(expressions)
+      );
+      write_line(out, depth, &line);
+    }
+    Statement::MethodInvocation {
+      identifier,
+      arguments,
+    } => {
+      let line = format!("{}({})", identifier, list_to_source(arguments));
+      write_line(out, depth, &line);
+    }
+    Statement::IfStatement {
+      if_blocks,
+      else_statements,
+    } => {
+      for (i, (condition, block)) in if_blocks.iter().enumerate() {
+        let keyword = if i == 0 { "If" } else { "Else If" };
+        let line = format!("{} {} Then", keyword, expression_to_source(condition));
+        write_line(out, depth, &line);
+        write_statements(out, block, depth + 1);
+      }
+      if !else_statements.is_empty() {
+        write_line(out, depth, "Else");
+        write_statements(out, else_statements, depth + 1);
+      }
+      write_line(out, depth, "End If");
+    }
+    Statement::ForStatement {
+      loop_counter,
+      loop_counter_from,
+      loop_counter_to,
+      block,
+    } => {
+      let line = format!(
+        "For {} = {} To {}",
+        loop_counter,
+        expression_to_source(loop_counter_from),
+        expression_to_source(loop_counter_to)
+      );
+      write_line(out, depth, &line);
+      write_statements(out, block, depth + 1);
+      write_line(out, depth, "Next");
+    }
+    Statement::DoLoop { block } => {
+      write_line(out, depth, "Do");
+      write_statements(out, block, depth + 1);
+      write_line(out, depth, "Loop");
+    }
+    Statement::WhileStatement { condition, block } => {
+      let line = format!("While {}", expression_to_source(condition));
+      write_line(out, depth, &line);
+      write_statements(out, block, depth + 1);
+      write_line(out, depth, "End While");
+    }
+    Statement::GuardStatement {
+      condition,
+      else_statements,
+    } => {
+      let line = format!("Guard {} Else", expression_to_source(condition));
+      write_line(out, depth, &line);
+      write_statements(out, else_statements, depth + 1);
+      write_line(out, depth, "End Guard");
+    }
+    Statement::ExitDo => write_line(out, depth, "Exit Do"),
+    Statement::Empty => write_line(out, depth, ""),
+  }
+}
+
+fn declaration_keyword(constant: bool) -> &'static str {
+  if constant {
+    "Const"
+  } else {
+    "Dim"
+  }
+}
+
+fn list_to_source(expressions: &[Expression]) -> String {
+  let sources: Vec<String> = expressions.iter().map(expression_to_source).collect();
+  sources.join(", ")
+}
+
+fn write_operator(out: &mut String, operator: &BinaryOperator) {
+  out.push(' ');
+  out.push_str(operator.spelling());
+  out.push(' ');
+}
+
+fn is_negative_literal(expression: &Expression) -> bool {
+  match expression {
+    Expression::Integer(n) => *n < 0,
+    Expression::Float(n) => n.is_sign_negative(),
+    _ => false,
+  }
+}
+
+// `min_precedence` is how tightly an operator has to bind to appear here
+// without parentheses. `following` is the precedence of the operator written
+// right after the expression, 0 if there is none: a leading `Not` or sign
+// would take that operator into its operand unless it is grouped.
+fn write_expression(out: &mut String, expression: &Expression, min_precedence: u8, following: u8) {
+  match expression {
+    Expression::Identifier(name) => out.push_str(name),
+    Expression::String(s) => write_string(out, s),
+    Expression::Integer(_) | Expression::Float(_)
+      if is_negative_literal(expression) && following >= UnaryOperator::NEGATIVE.precedence() =>
+    {
+      out.push('(');
+      write_expression(out, expression, 0, 0);
+      out.push(')');
+    }
+    Expression::Integer(n) => out.push_str(&n.to_string()),
+    // Display never uses exponents, which the lexer does not read.
+    Expression::Float(n) => {
+      let text = n.to_string();
+      out.push_str(&text);
+      if !text.contains('.') {
+        out.push_str(".0");
+      }
+    }
+    Expression::Boolean(b) => out.push_str(if *b { "true" } else { "false" }),
+    Expression::Unary {
+      operator,
+      expression,
+    } => {
+      let precedence = operator.precedence();
+      let grouped = following >= precedence;
+      if grouped {
+        out.push('(');
+      }
+      out.push_str(operator.spelling());
+      write_expression(
+        out,
+        expression,
+        precedence,
+        if grouped { 0 } else { following },
+      );
+      if grouped {
+        out.push(')');
+      }
+    }
+    Expression::Binary {
+      left,
+      operator,
+      right,
+    } => {
+      let precedence = operator.precedence();
+      let grouped = precedence < min_precedence;
+      let following = if grouped { 0 } else { following };
+      if grouped {
+        out.push('(');
+      }
+      if operator.is_right_associative() {
+        write_expression(out, left, precedence + 1, precedence);
+        write_operator(out, operator);
+        write_expression(out, right, precedence, following);
+      } else {
+        // Operators of equal precedence down the left, as in `1 + 2 - 3`, are
+        // written in a loop so that a long chain does not recurse per operator.
+        let mut rest = vec![(operator, right)];
+        let mut first = left;
+        while let Expression::Binary {
+          left,
+          operator,
+          right,
+        } = &**first
+        {
+          if operator.precedence() != precedence || operator.is_right_associative() {
+            break;
+          }
+          rest.push((operator, right));
+          first = left;
+        }
+        write_expression(out, first, precedence, precedence);
+        for (i, (operator, right)) in rest.iter().rev().enumerate() {
+          write_operator(out, operator);
+          let next = if i + 1 < rest.len() {
+            precedence
+          } else {
+            following
+          };
+          write_expression(out, right, precedence + 1, next);
+        }
+      }
+      if grouped {
+        out.push(')');
+      }
+    }
+    Expression::MethodInvocation {
+      identifier,
+      arguments,
+    } => {
+      out.push_str(identifier);
+      out.push('(');
+      out.push_str(&list_to_source(arguments));
+      out.push(')');
+    }
+    Expression::Sequence(expressions) => {
+      out.push('(');
+      out.push_str(&list_to_source(expressions));
+      out.push(')');
+    }
+    Expression::Array(elements) => {
+      out.push('[');
+      out.push_str(&list_to_source(elements));
+      out.push(']');
+    }
+    Expression::Spread(expression) => {
+      out.push_str("...");
+      write_expression(out, expression, 0, 0);
+    }
+    // Only a primary can be indexed, so anything with an operator is grouped.
+    Expression::Index { expression, index } => {
+      let grouped = is_negative_literal(expression)
+        || matches!(
+          **expression,
+          Expression::Unary { .. } | Expression::Binary { .. }
+        );
+      if grouped {
+        out.push('(');
+      }
+      write_expression(out, expression, 0, 0);
+      if grouped {
+        out.push(')');
+      }
+      out.push('[');
+      write_expression(out, index, 0, 0);
+      out.push(']');
+    }
+  }
+}
+
+fn write_string(out: &mut String, s: &str) {
+  out.push('"');
+  for c in s.chars() {
+    match c {
+      '\n' => out.push_str("\\n"),
+      '\t' => out.push_str("\\t"),
+      '"' => out.push_str("\\\""),
+      '\\' => out.push_str("\\\\"),
+      c => out.push(c),
+    }
+  }
+  out.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::parser::parse_source;
+
+  fn parse(source: &str) -> Program {
+    parse_source(source, "<test>").unwrap()
+  }
+
+  #[test]
+  fn only_needed_parentheses_are_written() {
+    assert_eq!(
+      to_source(&parse("Dim x = (2 + 3) * 4")),
+      "Dim x = (2 + 3) * 4\n"
+    );
+    assert_eq!(
+      to_source(&parse("Dim x = 2 + (3 * 4)")),
+      "Dim x = 2 + 3 * 4\n"
+    );
+    assert_eq!(
+      to_source(&parse("Dim x = 10 - (3 - 2)")),
+      "Dim x = 10 - (3 - 2)\n"
+    );
+    assert_eq!(
+      to_source(&parse("Dim x = (10 - 3) - 2")),
+      "Dim x = 10 - 3 - 2\n"
+    );
+  }
+
+  #[test]
+  fn printed_programs_parse_back_to_the_same_tree() {
+    let programs = [
+      "Const x = -(2 + 3) * 4 Mod 3\nDim s = \"a\\n\\\"b\\\"\" + \"c\"\nPrint(s, Not x > 1 And true)",
+      "Dim a, b = [1, ...[2, 3]], 2.5\na, b = b, a\nDim n = a[0]",
+      "For i = 1 To 3\n  If i = 2 Then\n    Print(i)\n  Else If i <> 1 Then\n    EPrint(If(i >= 3, \"big\", \"small\"))\n  Else\n    Dim y = (i, i + 1)\n  End If\nNext",
+      "Dim i = 0\nDo\n  Guard i < 3 Else\n    Exit Do\n  End Guard\n  While false\n  End While\n  i = i + 1\nLoop",
+    ];
+    for source in programs.iter() {
+      let program = parse(source);
+      let printed = to_source(&program);
+      assert_eq!(parse(&printed), program, "{}", printed);
+    }
+  }
+
+  #[test]
+  fn a_ten_thousand_term_chain_is_printed() {
+    let source = format!("Dim x = 0{}\n", " + 2 * 3 - 4".repeat(3333));
+    let program = parse(&source);
+    assert_eq!(to_source(&program), source);
+  }
+}
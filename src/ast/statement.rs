@@ -1,16 +1,31 @@
-use super::expression::Expression;
+use super::expression::{Expression, ExpressionList};
+use serde::{Deserialize, Serialize};
 use std::fmt;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[allow(clippy::enum_variant_names)]
 pub enum Statement {
   Declaration {
     identifier: String,
     expression: Expression,
+    // `Const` rather than `Dim`.
+    #[serde(default)]
+    constant: bool,
   },
   Assignment {
     identifier: String,
     expression: Expression,
   },
+  MultipleDeclaration {
+    identifiers: Vec<String>,
+    expressions: Vec<Expression>,
+    #[serde(default)]
+    constant: bool,
+  },
+  MultipleAssignment {
+    identifiers: Vec<String>,
+    expressions: Vec<Expression>,
+  },
   MethodInvocation {
     identifier: String,
     arguments: Vec<Expression>,
@@ -25,23 +40,103 @@ pub enum Statement {
     loop_counter_to: Expression,
     block: Vec<Statement>,
   },
+  DoLoop {
+    block: Vec<Statement>,
+  },
+  WhileStatement {
+    condition: Expression,
+    block: Vec<Statement>,
+  },
+  GuardStatement {
+    condition: Expression,
+    else_statements: Vec<Statement>,
+  },
+  ExitDo,
   Empty,
 }
+impl Statement {
+  fn declaration_keyword(constant: bool) -> &'static str {
+    if constant {
+      "Const"
+    } else {
+      "Dim"
+    }
+  }
+
+  // This statement and every statement nested in it.
+  pub fn statement_count(&self) -> usize {
+    1 + self
+      .nested_blocks()
+      .iter()
+      .flat_map(|block| block.iter())
+      .map(Statement::statement_count)
+      .sum::<usize>()
+  }
+
+  // The statement blocks directly inside this statement, in source order.
+  pub fn nested_blocks(&self) -> Vec<&[Statement]> {
+    match self {
+      Statement::IfStatement {
+        if_blocks,
+        else_statements,
+      } => {
+        let mut blocks: Vec<&[Statement]> = if_blocks.iter().map(|(_, b)| b.as_slice()).collect();
+        blocks.push(else_statements);
+        blocks
+      }
+      Statement::ForStatement { block, .. } => vec![block],
+      Statement::DoLoop { block } => vec![block],
+      Statement::WhileStatement { block, .. } => vec![block],
+      Statement::GuardStatement {
+        else_statements, ..
+      } => vec![else_statements],
+      _ => vec![],
+    }
+  }
+}
+
 impl fmt::Display for Statement {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
     match self {
       Statement::Declaration {
         identifier,
         expression,
-      } => writeln!(f, "Const {} = {}", identifier, expression)?,
+        constant,
+      } => writeln!(
+        f,
+        "{} {} = {}",
+        Self::declaration_keyword(*constant),
+        identifier,
+        expression
+      )?,
       Statement::Assignment {
         identifier,
         expression,
       } => writeln!(f, "{} = {}", identifier, expression)?,
+      Statement::MultipleDeclaration {
+        identifiers,
+        expressions,
+        constant,
+      } => writeln!(
+        f,
+        "{} {} = {}",
+        Self::declaration_keyword(*constant),
+        identifiers.join(", "),
+        ExpressionList(expressions)
+      )?,
+      Statement::MultipleAssignment {
+        identifiers,
+        expressions,
+      } => writeln!(
+        f,
+        "{} = {}",
+        identifiers.join(", "),
+        ExpressionList(expressions)
+      )?,
       Statement::MethodInvocation {
         identifier,
         arguments,
-      } => writeln!(f, "{}({:?})", identifier, arguments)?,
+      } => writeln!(f, "{}({})", identifier, ExpressionList(arguments))?,
       Statement::ForStatement {
         loop_counter,
         loop_counter_from,
@@ -58,6 +153,31 @@ impl fmt::Display for Statement {
         }
         writeln!(f, "Next")?;
       }
+      Statement::DoLoop { block } => {
+        writeln!(f, "Do")?;
+        for s in block {
+          writeln!(f, "{}", s)?;
+        }
+        writeln!(f, "Loop")?;
+      }
+      Statement::WhileStatement { condition, block } => {
+        writeln!(f, "While {}", condition)?;
+        for s in block {
+          writeln!(f, "{}", s)?;
+        }
+        writeln!(f, "End While")?;
+      }
+      Statement::GuardStatement {
+        condition,
+        else_statements,
+      } => {
+        writeln!(f, "Guard {} Else", condition)?;
+        for s in else_statements {
+          writeln!(f, "{}", s)?;
+        }
+        writeln!(f, "End Guard")?;
+      }
+      Statement::ExitDo => writeln!(f, "Exit Do")?,
       Statement::IfStatement {
         if_blocks,
         else_statements,
@@ -68,7 +188,7 @@ impl fmt::Display for Statement {
             writeln!(f, "{}", s)?;
           }
         }
-        if 0 < else_statements.len() {
+        if !else_statements.is_empty() {
           writeln!(f, "Else")?;
           for s in else_statements {
             writeln!(f, "{}", s)?;
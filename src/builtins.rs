@@ -0,0 +1,498 @@
+use super::ast::BinaryOperator;
+use super::object::{Object, RuntimeType, TypeOf};
+use super::runtime_error::RuntimeError;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::format::{Item, StrftimeItems};
+use chrono::DateTime;
+use regex::Regex;
+use serde_json::Value;
+use std::convert::TryFrom;
+use std::rc::Rc;
+
+fn expect_string(value: &Object) -> Result<&str, RuntimeError> {
+  match value {
+    Object::String(s) => Ok(s),
+    _ => Err(RuntimeError::TypeMismatch {
+      expected: RuntimeType::String,
+      actual: value.type_of(),
+    }),
+  }
+}
+
+fn expect_integer(value: &Object) -> Result<i32, RuntimeError> {
+  match value {
+    Object::Integer(n) => Ok(*n),
+    _ => Err(RuntimeError::TypeMismatch {
+      expected: RuntimeType::Integer,
+      actual: value.type_of(),
+    }),
+  }
+}
+
+pub fn base64_encode(value: &Object) -> Result<Object, RuntimeError> {
+  let s = expect_string(value)?;
+  Ok(Object::String(STANDARD.encode(s)))
+}
+
+pub fn base64_decode(value: &Object) -> Result<Object, RuntimeError> {
+  let s = expect_string(value)?;
+  let bytes = STANDARD
+    .decode(s)
+    .map_err(|e| RuntimeError::InvalidConversion(format!("invalid base64: {}", e)))?;
+  let decoded = String::from_utf8(bytes).map_err(|_| {
+    RuntimeError::InvalidConversion(String::from("decoded base64 is not valid UTF-8"))
+  })?;
+  Ok(Object::String(decoded))
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+// 64-bit FNV-1a, stable across runs and platforms.
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+  let mut hash = FNV_OFFSET_BASIS;
+  for b in bytes {
+    hash ^= u64::from(*b);
+    hash = hash.wrapping_mul(FNV_PRIME);
+  }
+  hash
+}
+
+// Integers are 32-bit, so only the low 32 bits of the FNV-1a hash are kept.
+pub fn hash(value: &Object) -> Result<Object, RuntimeError> {
+  let s = expect_string(value)?;
+  Ok(Object::Integer(fnv1a(s.as_bytes()) as i32))
+}
+
+fn from_json(value: Value) -> Result<Object, RuntimeError> {
+  match value {
+    Value::Null => Ok(Object::Undefined),
+    Value::Bool(b) => Ok(Object::Boolean(b)),
+    Value::Number(n) => match (n.as_i64(), n.as_f64()) {
+      (Some(i), _) => match i32::try_from(i) {
+        Ok(i) => Ok(Object::Integer(i)),
+        Err(_) => Err(RuntimeError::InvalidConversion(format!(
+          "JSON number {} is not a 32-bit integer",
+          n
+        ))),
+      },
+      (None, Some(f)) => Ok(Object::Float(f)),
+      (None, None) => Err(RuntimeError::InvalidConversion(format!(
+        "JSON number {} is not supported",
+        n
+      ))),
+    },
+    Value::String(s) => Ok(Object::String(s)),
+    Value::Array(values) => {
+      let mut elements = vec![];
+      for v in values {
+        elements.push(from_json(v)?);
+      }
+      Ok(Object::Array(Rc::new(elements)))
+    }
+    Value::Object(_) => Err(RuntimeError::InvalidConversion(String::from(
+      "JSON objects are not supported",
+    ))),
+  }
+}
+
+fn to_json(value: &Object) -> Value {
+  match value {
+    Object::Undefined | Object::Unit => Value::Null,
+    Object::Integer(n) => Value::from(*n),
+    Object::Float(n) => Value::from(*n),
+    Object::Boolean(b) => Value::Bool(*b),
+    Object::String(s) | Object::StringBuilder(s) => Value::String(s.clone()),
+    Object::Array(elements) => Value::Array(elements.iter().map(to_json).collect()),
+    Object::Heap(heap) => Value::Array(heap.sorted().into_iter().map(Value::from).collect()),
+    Object::Set(keys) => Value::Array(keys.iter().map(|k| to_json(&k.to_object())).collect()),
+  }
+}
+
+pub fn json_parse(value: &Object) -> Result<Object, RuntimeError> {
+  let s = expect_string(value)?;
+  let json = serde_json::from_str(s)
+    .map_err(|e| RuntimeError::InvalidConversion(format!("invalid JSON: {}", e)))?;
+  from_json(json)
+}
+
+pub fn json_stringify(value: &Object) -> Result<Object, RuntimeError> {
+  Ok(Object::String(to_json(value).to_string()))
+}
+
+// RFC 4180 style: fields may be quoted to contain commas, newlines and
+// doubled quotes (""); rows end with LF or CRLF.
+pub fn csv_parse(value: &Object) -> Result<Object, RuntimeError> {
+  let chars: Vec<char> = expect_string(value)?.chars().collect();
+  let mut rows = vec![];
+  let mut row = vec![];
+  let mut field = String::new();
+  let mut field_started = false;
+  let mut line = 1;
+  let mut i = 0;
+  while i < chars.len() {
+    match chars[i] {
+      '"' if !field_started => {
+        field_started = true;
+        i += 1;
+        loop {
+          match chars.get(i) {
+            None => {
+              return Err(RuntimeError::InvalidConversion(format!(
+                "unterminated quoted CSV field on line {}",
+                line
+              )))
+            }
+            Some('"') if chars.get(i + 1) == Some(&'"') => {
+              field.push('"');
+              i += 2;
+            }
+            Some('"') => {
+              i += 1;
+              break;
+            }
+            Some(c) => {
+              if *c == '\n' {
+                line += 1;
+              }
+              field.push(*c);
+              i += 1;
+            }
+          }
+        }
+        match chars.get(i) {
+          None | Some(',') | Some('\n') | Some('\r') => {}
+          Some(c) => {
+            return Err(RuntimeError::InvalidConversion(format!(
+              "unexpected '{}' after closing quote in CSV on line {}",
+              c, line
+            )))
+          }
+        }
+        continue;
+      }
+      '"' => {
+        return Err(RuntimeError::InvalidConversion(format!(
+          "unexpected quote in unquoted CSV field on line {}",
+          line
+        )))
+      }
+      ',' => {
+        row.push(Object::String(std::mem::take(&mut field)));
+        field_started = false;
+      }
+      '\r' if chars.get(i + 1) == Some(&'\n') => {}
+      '\n' => {
+        row.push(Object::String(std::mem::take(&mut field)));
+        rows.push(Object::Array(Rc::new(std::mem::take(&mut row))));
+        field_started = false;
+        line += 1;
+      }
+      c => {
+        field.push(c);
+        field_started = true;
+      }
+    }
+    i += 1;
+  }
+  if field_started || !row.is_empty() {
+    row.push(Object::String(field));
+    rows.push(Object::Array(Rc::new(row)));
+  }
+  Ok(Object::Array(Rc::new(rows)))
+}
+
+fn compile_regex(pattern: &Object) -> Result<Regex, RuntimeError> {
+  let pattern = expect_string(pattern)?;
+  Regex::new(pattern).map_err(|e| RuntimeError::InvalidRegex(e.to_string()))
+}
+
+pub fn regex_match(pattern: &Object, text: &Object) -> Result<Object, RuntimeError> {
+  let re = compile_regex(pattern)?;
+  Ok(Object::Boolean(re.is_match(expect_string(text)?)))
+}
+
+pub fn regex_find(pattern: &Object, text: &Object) -> Result<Object, RuntimeError> {
+  let re = compile_regex(pattern)?;
+  match re.find(expect_string(text)?) {
+    Some(m) => Ok(Object::String(m.as_str().to_string())),
+    None => Ok(Object::Undefined),
+  }
+}
+
+// Replaces every match; `$1` or `${name}` in the replacement refer to capture groups.
+pub fn regex_replace(
+  pattern: &Object,
+  text: &Object,
+  replacement: &Object,
+) -> Result<Object, RuntimeError> {
+  let re = compile_regex(pattern)?;
+  let replaced = re.replace_all(expect_string(text)?, expect_string(replacement)?);
+  Ok(Object::String(replaced.into_owned()))
+}
+
+// Renders a Unix timestamp in UTC with strftime-style specifiers such as `%Y-%m-%d`.
+pub fn format_time(seconds: &Object, format: &Object) -> Result<Object, RuntimeError> {
+  format_unix_time(i64::from(expect_integer(seconds)?), format)
+}
+
+pub fn format_unix_time(seconds: i64, format: &Object) -> Result<Object, RuntimeError> {
+  let format = expect_string(format)?;
+  let items: Vec<Item> = StrftimeItems::new(format).collect();
+  if items.contains(&Item::Error) {
+    return Err(RuntimeError::InvalidArgument(format!(
+      "invalid time format '{}'",
+      format
+    )));
+  }
+  let time = DateTime::from_timestamp(seconds, 0).ok_or_else(|| {
+    RuntimeError::InvalidArgument(format!("{} seconds is out of range for a time", seconds))
+  })?;
+  Ok(Object::String(
+    time.format_with_items(items.iter()).to_string(),
+  ))
+}
+
+// -0.0 has sign 0; NaN has none and is an InvalidArgument.
+pub fn sign(value: &Object) -> Result<Object, RuntimeError> {
+  match value {
+    Object::Float(n) if n.is_nan() => Err(RuntimeError::InvalidArgument(
+      "Sign of NaN is undefined".to_string(),
+    )),
+    Object::Float(n) if *n == 0.0 => Ok(Object::Integer(0)),
+    Object::Float(n) => Ok(Object::Integer(n.signum() as i32)),
+    _ => Ok(Object::Integer(expect_integer(value)?.signum())),
+  }
+}
+
+// Returns `[quotient, remainder]`, truncating like `/` and `Mod`.
+pub fn div_mod(a: &Object, b: &Object) -> Result<Object, RuntimeError> {
+  let (a, b) = (expect_integer(a)?, expect_integer(b)?);
+  if b == 0 {
+    return Err(RuntimeError::DivisionByZero);
+  }
+  match (a.checked_div(b), a.checked_rem(b)) {
+    (Some(q), Some(r)) => Ok(Object::Array(Rc::new(vec![
+      Object::Integer(q),
+      Object::Integer(r),
+    ]))),
+    _ => Err(RuntimeError::IntegerOverflow {
+      operator: BinaryOperator::DIV,
+    }),
+  }
+}
+
+// Overflows like negation for the smallest Integer; Abs of NaN is NaN.
+pub fn abs(value: &Object) -> Result<Object, RuntimeError> {
+  match value {
+    Object::Float(n) => Ok(Object::Float(n.abs())),
+    _ => expect_integer(value)?
+      .checked_abs()
+      .map(Object::Integer)
+      .ok_or(RuntimeError::IntegerOverflow {
+        operator: BinaryOperator::SUB,
+      }),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn string(s: &str) -> Object {
+    Object::String(s.to_string())
+  }
+
+  fn is_invalid_conversion(result: Result<Object, RuntimeError>) -> bool {
+    matches!(result, Err(RuntimeError::InvalidConversion(_)))
+  }
+
+  #[test]
+  fn base64_round_trips() {
+    let encoded = base64_encode(&string("héllo, world")).unwrap();
+    assert_eq!(encoded, string("aMOpbGxvLCB3b3JsZA=="));
+    assert_eq!(base64_decode(&encoded), Ok(string("héllo, world")));
+    assert_eq!(base64_encode(&string("")), Ok(string("")));
+  }
+
+  #[test]
+  fn base64_decode_rejects_malformed_input() {
+    assert!(is_invalid_conversion(base64_decode(&string("not base64!"))));
+    assert!(is_invalid_conversion(base64_decode(&string("/w=="))));
+  }
+
+  #[test]
+  fn fnv1a_matches_known_values() {
+    assert_eq!(fnv1a(b""), 0xcbf2_9ce4_8422_2325);
+    assert_eq!(fnv1a(b"a"), 0xaf63_dc4c_8601_ec8c);
+    assert_eq!(fnv1a(b"foobar"), 0x8594_4171_f739_67e8);
+  }
+
+  #[test]
+  fn hash_keeps_the_low_32_bits() {
+    assert_eq!(
+      hash(&string("a")),
+      Ok(Object::Integer(0x8601_ec8c_u32 as i32))
+    );
+    assert_eq!(hash(&string("foobar")), Ok(Object::Integer(-147_232_792)));
+    assert_eq!(hash(&string("foobar")), hash(&string("foobar")));
+  }
+
+  #[test]
+  fn json_round_trips_nested_arrays() {
+    let source = r#"[1,2.5,true,"x",[null,["deep"]]]"#;
+    let parsed = json_parse(&string(source)).unwrap();
+    assert_eq!(
+      parsed,
+      Object::Array(Rc::new(vec![
+        Object::Integer(1),
+        Object::Float(2.5),
+        Object::Boolean(true),
+        string("x"),
+        Object::Array(Rc::new(vec![
+          Object::Undefined,
+          Object::Array(Rc::new(vec![string("deep")])),
+        ])),
+      ]))
+    );
+    assert_eq!(json_stringify(&parsed), Ok(string(source)));
+  }
+
+  #[test]
+  fn json_parse_rejects_invalid_input() {
+    assert!(is_invalid_conversion(json_parse(&string("[1, 2"))));
+    assert!(is_invalid_conversion(json_parse(&string("4294967296"))));
+    assert!(is_invalid_conversion(json_parse(&string(r#"{"a": 1}"#))));
+  }
+
+  fn table(rows: &[&[&str]]) -> Object {
+    Object::Array(Rc::new(
+      rows
+        .iter()
+        .map(|row| Object::Array(Rc::new(row.iter().map(|f| string(f)).collect())))
+        .collect(),
+    ))
+  }
+
+  #[test]
+  fn csv_parses_a_simple_table() {
+    assert_eq!(
+      csv_parse(&string("a,b,c\r\n1,2,3\n")),
+      Ok(table(&[&["a", "b", "c"], &["1", "2", "3"]]))
+    );
+    assert_eq!(csv_parse(&string("x,\n")), Ok(table(&[&["x", ""]])));
+  }
+
+  #[test]
+  fn csv_quoted_fields_keep_commas_newlines_and_quotes() {
+    assert_eq!(
+      csv_parse(&string("\"a,b\",\"line\nbreak\",\"say \"\"hi\"\"\"")),
+      Ok(table(&[&["a,b", "line\nbreak", "say \"hi\""]]))
+    );
+  }
+
+  #[test]
+  fn csv_rejects_malformed_quoting() {
+    assert!(is_invalid_conversion(csv_parse(&string("a,\"open\n"))));
+    assert!(is_invalid_conversion(csv_parse(&string("\"a\"b,c"))));
+    assert!(is_invalid_conversion(csv_parse(&string("a\"b,c"))));
+  }
+
+  #[test]
+  fn regex_match_and_find() {
+    let digits = string("[0-9]+");
+    assert_eq!(
+      regex_match(&digits, &string("abc 42")),
+      Ok(Object::Boolean(true))
+    );
+    assert_eq!(
+      regex_match(&digits, &string("abc")),
+      Ok(Object::Boolean(false))
+    );
+    assert_eq!(regex_find(&digits, &string("a 12 b 345")), Ok(string("12")));
+    assert_eq!(regex_find(&digits, &string("none")), Ok(Object::Undefined));
+    assert_eq!(
+      regex_find(&string("(\\w+)@(\\w+)"), &string("mail me@host now")),
+      Ok(string("me@host"))
+    );
+  }
+
+  #[test]
+  fn invalid_patterns_are_reported() {
+    for result in [
+      regex_match(&string("(unclosed"), &string("x")),
+      regex_find(&string("[z-a]"), &string("x")),
+    ] {
+      assert!(matches!(result, Err(RuntimeError::InvalidRegex(_))));
+    }
+  }
+
+  #[test]
+  fn regex_replace_expands_capture_groups() {
+    assert_eq!(
+      regex_replace(
+        &string("(\\w+)@(\\w+)"),
+        &string("a@b, c@d"),
+        &string("$2 at ${1}")
+      ),
+      Ok(string("b at a, d at c"))
+    );
+    assert_eq!(
+      regex_replace(&string("[0-9]"), &string("no digits"), &string("#")),
+      Ok(string("no digits"))
+    );
+    assert!(matches!(
+      regex_replace(&string("*"), &string("x"), &string("y")),
+      Err(RuntimeError::InvalidRegex(_))
+    ));
+  }
+
+  #[test]
+  fn sign_and_abs_of_integers() {
+    for (n, expected) in [(-7, -1), (0, 0), (42, 1), (i32::MIN, -1)] {
+      assert_eq!(sign(&Object::Integer(n)), Ok(Object::Integer(expected)));
+    }
+    assert_eq!(abs(&Object::Integer(-7)), Ok(Object::Integer(7)));
+    assert_eq!(abs(&Object::Integer(7)), Ok(Object::Integer(7)));
+    assert_eq!(
+      abs(&Object::Integer(i32::MIN)),
+      Err(RuntimeError::IntegerOverflow {
+        operator: BinaryOperator::SUB,
+      })
+    );
+    assert_eq!(
+      sign(&string("-1")),
+      Err(RuntimeError::TypeMismatch {
+        expected: RuntimeType::Integer,
+        actual: RuntimeType::String,
+      })
+    );
+    assert!(matches!(
+      abs(&Object::Boolean(true)),
+      Err(RuntimeError::TypeMismatch { .. })
+    ));
+  }
+
+  #[test]
+  fn sign_and_abs_of_floats() {
+    for (n, expected) in [
+      (-2.5, -1),
+      (0.0, 0),
+      (-0.0, 0),
+      (0.5, 1),
+      (f64::NEG_INFINITY, -1),
+    ] {
+      assert_eq!(sign(&Object::Float(n)), Ok(Object::Integer(expected)));
+    }
+    assert!(matches!(
+      sign(&Object::Float(f64::NAN)),
+      Err(RuntimeError::InvalidArgument(_))
+    ));
+    assert_eq!(abs(&Object::Float(-2.5)), Ok(Object::Float(2.5)));
+    match abs(&Object::Float(-0.0)) {
+      Ok(Object::Float(n)) => assert!(n == 0.0 && n.is_sign_positive()),
+      r => panic!("expected a Float, got {:?}", r),
+    }
+    assert!(matches!(abs(&Object::Float(f64::NAN)), Ok(Object::Float(n)) if n.is_nan()));
+  }
+}
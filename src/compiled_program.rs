@@ -0,0 +1,68 @@
+use super::ast::program::Program;
+use super::executor::Executor;
+use super::object::Object;
+use super::parse_error::ParseError;
+use super::parser;
+use super::runtime_error::RuntimeError;
+use std::collections::BTreeMap;
+
+// A program parsed once and executed any number of times, e.g. by a
+// templating engine rendering the same script with different data.
+pub struct CompiledProgram {
+  program: Program,
+}
+
+impl CompiledProgram {
+  pub fn compile(source: &str, file_name: &str) -> Result<CompiledProgram, ParseError> {
+    let program = parser::parse_source(source, file_name)?;
+    Ok(CompiledProgram { program })
+  }
+
+  pub fn program(&self) -> &Program {
+    &self.program
+  }
+
+  pub fn execute(&self, executor: &mut Executor) -> Result<Object, RuntimeError> {
+    executor.execute(&self.program)
+  }
+
+  pub fn execute_with_env(
+    &self,
+    executor: &mut Executor,
+    env: BTreeMap<String, Object>,
+  ) -> Result<Object, RuntimeError> {
+    executor.execute_with_env(&self.program, env)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn env(x: i32) -> BTreeMap<String, Object> {
+    vec![("x".to_string(), Object::Integer(x))]
+      .into_iter()
+      .collect()
+  }
+
+  #[test]
+  fn one_compiled_program_runs_with_different_environments() {
+    let compiled = CompiledProgram::compile("Dim y = x * 2", "<test>").unwrap();
+    let mut first = Executor::new();
+    let mut second = Executor::new();
+    assert_eq!(
+      compiled.execute_with_env(&mut first, env(10)),
+      Ok(Object::Integer(20))
+    );
+    assert_eq!(
+      compiled.execute_with_env(&mut second, env(-3)),
+      Ok(Object::Integer(-6))
+    );
+    assert_eq!(first.get_variable("y"), Some(Object::Integer(20)));
+  }
+
+  #[test]
+  fn compile_reports_parse_errors() {
+    assert!(CompiledProgram::compile("Dim = 1", "<test>").is_err());
+  }
+}
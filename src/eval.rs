@@ -0,0 +1,136 @@
+use super::executor::Executor;
+use super::object::Object;
+use super::parse_error::ParseError;
+use super::parser;
+use super::runtime_error::RuntimeError;
+use std::cell::RefCell;
+use std::fmt;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+#[derive(Debug, PartialEq)]
+pub enum EvalError {
+  Parse(ParseError),
+  Runtime(RuntimeError),
+}
+impl fmt::Display for EvalError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      EvalError::Parse(e) => write!(f, "Compile error: {}", e),
+      EvalError::Runtime(e) => write!(f, "Execution error: {}", e),
+    }
+  }
+}
+
+#[derive(Debug)]
+pub struct RunResult {
+  pub value: Object,
+  pub stdout: String,
+  pub stderr: String,
+  pub error: Option<EvalError>,
+}
+
+#[derive(Clone, Default)]
+struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl SharedBuffer {
+  fn contents(&self) -> String {
+    String::from_utf8_lossy(&self.0.borrow()).into_owned()
+  }
+}
+
+impl Write for SharedBuffer {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    self.0.borrow_mut().extend_from_slice(buf);
+    Ok(buf.len())
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    Ok(())
+  }
+}
+
+/// Parses and runs `source` on a fresh Executor and returns the value of its
+/// last statement.
+///
+/// ```
+/// use bsharp_lang::eval_str;
+/// use bsharp_lang::object::Object;
+///
+/// let value = eval_str("Const x = 21\nDim y = x + x");
+/// assert_eq!(value, Ok(Object::Integer(42)));
+/// ```
+pub fn eval_str(source: &str) -> Result<Object, EvalError> {
+  let program = parser::parse_source(source, "<eval>").map_err(EvalError::Parse)?;
+  Executor::new()
+    .execute(&program)
+    .map_err(EvalError::Runtime)
+}
+
+// Parses and runs `source` on a fresh Executor, capturing everything it prints.
+pub fn run_capturing(source: &str) -> RunResult {
+  let stdout = SharedBuffer::default();
+  let stderr = SharedBuffer::default();
+  let mut executor = Executor::new();
+  executor.set_output(Box::new(stdout.clone()));
+  executor.set_error_output(Box::new(stderr.clone()));
+  let (value, error) = match parser::parse_source(source, "<run>") {
+    Ok(program) => match executor.execute(&program) {
+      Ok(value) => (value, None),
+      Err(e) => (Object::Undefined, Some(EvalError::Runtime(e))),
+    },
+    Err(e) => (Object::Undefined, Some(EvalError::Parse(e))),
+  };
+  RunResult {
+    value,
+    stdout: stdout.contents(),
+    stderr: stderr.contents(),
+    error,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn run_capturing_collects_value_and_output() {
+    let result = run_capturing("Print(\"sum\")\nEPrint(\"note\")\nDim x = 2 + 3");
+    assert_eq!(result.value, Object::Integer(5));
+    assert_eq!(result.stdout, "sum\n");
+    assert_eq!(result.stderr, "note\n");
+    assert_eq!(result.error, None);
+  }
+
+  #[test]
+  fn run_capturing_keeps_output_printed_before_an_error() {
+    let result = run_capturing("Print(1)\nDim x = 1 / 0");
+    assert_eq!(result.value, Object::Undefined);
+    assert_eq!(result.stdout, "Integer(1)\n");
+    assert_eq!(
+      result.error,
+      Some(EvalError::Runtime(RuntimeError::DivisionByZero))
+    );
+    assert!(matches!(
+      run_capturing("Dim = 1").error,
+      Some(EvalError::Parse(_))
+    ));
+  }
+
+  #[test]
+  fn eval_str_returns_the_last_value_or_one_error_type() {
+    assert_eq!(
+      eval_str("Dim x = 2\nDim y = x * 21"),
+      Ok(Object::Integer(42))
+    );
+    assert!(matches!(eval_str("Dim = 1"), Err(EvalError::Parse(_))));
+    assert_eq!(
+      eval_str("Dim x = 1 / 0"),
+      Err(EvalError::Runtime(RuntimeError::DivisionByZero))
+    );
+    assert_eq!(
+      EvalError::Runtime(RuntimeError::DivisionByZero).to_string(),
+      format!("Execution error: {}", RuntimeError::DivisionByZero)
+    );
+  }
+}
@@ -1,37 +1,633 @@
 use super::ast::{
   expression::Expression, program::Program, statement::Statement, BinaryOperator, UnaryOperator,
 };
-use super::object::{Add, Object, RuntimeType, TypeOf};
+use super::builtins;
+use super::object::{Add, Heap, HeapSize, Object, RuntimeType, SetKey, Truthy, TypeOf};
 use super::runtime_error::RuntimeError;
+use super::timer::{Clock, Timer};
+#[cfg(not(feature = "wasm"))]
+use super::timer::{InstantClock, ThreadTimer};
 use log::debug;
-use std::collections::BTreeMap;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryFrom;
+use std::io::{self, Write};
+use std::rc::Rc;
+use std::time::Duration;
+
+// Every builtin handled by execute_method, in the same order.
+const METHODS: [&str; 40] = [
+  "Print",
+  "Debug",
+  "EPrint",
+  "Flush",
+  "Unset",
+  "Inc",
+  "Dec",
+  "Halt",
+  "Heap",
+  "HeapPush",
+  "HeapPop",
+  "HeapPeek",
+  "Set",
+  "SetAdd",
+  "SetHas",
+  "SetRemove",
+  "StringBuilder",
+  "Append",
+  "Build",
+  "Len",
+  "Version",
+  "Methods",
+  "AssertEq",
+  "If",
+  "TypeOf",
+  "Truthy",
+  "Sleep",
+  "Base64Encode",
+  "Base64Decode",
+  "Hash",
+  "Sign",
+  "DivMod",
+  "Abs",
+  "JsonParse",
+  "JsonStringify",
+  "CsvParse",
+  "RegexMatch",
+  "RegexFind",
+  "RegexReplace",
+  "FormatTime",
+];
+
+pub type WatchCallback = Box<dyn FnMut(&Object, &Object)>;
+
+// Shared so that execute_readonly can run with the host's timer and clock.
+type SharedTimer = Rc<RefCell<Box<dyn Timer>>>;
+type SharedClock = Rc<RefCell<Box<dyn Clock>>>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatchHandle(usize);
+
+#[derive(Clone, Copy)]
+enum Binding {
+  Assign,
+  Dim,
+  Const,
+}
+
+// How `+` treats a String and an Integer operand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StringPlusInt {
+  Error,
+  Concat,
+  ParseAdd,
+}
+
+// What reading a variable that was never assigned yields. Error is the
+// default so typos surface; the others suit more lenient dialects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UndefinedRead {
+  Undefined,
+  Zero,
+  Error,
+}
+
+// What Float arithmetic does when a result is NaN or infinite. Ieee keeps the
+// special value, as f64 does; Error stops with FloatingPointError.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FloatPolicy {
+  Ieee,
+  Error,
+}
 
 pub struct Executor {
-  variables: BTreeMap<String, Object>,
+  // The innermost block's scope is last; the first one is never popped.
+  scopes: Vec<BTreeMap<String, Object>>,
+  // The names declared with `Const` in each scope.
+  constants: Vec<BTreeSet<String>>,
+  strict: bool,
+  case_insensitive: bool,
+  exiting_do: bool,
+  disabled_methods: BTreeSet<&'static str>,
+  timer: Option<SharedTimer>,
+  clock: Option<SharedClock>,
+  profiling: bool,
+  statement_timings: Vec<(usize, Duration)>,
+  coverage: bool,
+  covered_statements: BTreeSet<usize>,
+  // The preorder number of the statement being executed, kept up to date
+  // only while coverage is on.
+  statement_index: usize,
+  watchers: Vec<(WatchHandle, String, WatchCallback)>,
+  next_watch_id: usize,
+  output: Box<dyn Write>,
+  error_output: Box<dyn Write>,
+  buffered: bool,
+  flush_each_print: bool,
+  output_buffer: Vec<u8>,
+  memory_limit: Option<usize>,
+  max_loop_iterations: Option<usize>,
+  float_precision: Option<usize>,
+  undefined_read: UndefinedRead,
+  string_plus_int: StringPlusInt,
+  float_policy: FloatPolicy,
+  integral_float_indices: bool,
+}
+
+impl Default for Executor {
+  fn default() -> Executor {
+    Executor::new()
+  }
 }
 
 impl Executor {
   pub fn new() -> Executor {
-    return Executor {
-      variables: BTreeMap::new(),
-    };
+    Executor {
+      scopes: vec![BTreeMap::new()],
+      constants: vec![BTreeSet::new()],
+      strict: false,
+      case_insensitive: false,
+      exiting_do: false,
+      disabled_methods: BTreeSet::new(),
+      timer: Self::default_timer(),
+      clock: Self::default_clock(),
+      profiling: false,
+      statement_timings: vec![],
+      coverage: false,
+      covered_statements: BTreeSet::new(),
+      statement_index: 0,
+      watchers: vec![],
+      next_watch_id: 0,
+      output: Self::default_output(),
+      error_output: Self::default_error_output(),
+      buffered: false,
+      flush_each_print: false,
+      output_buffer: vec![],
+      memory_limit: None,
+      max_loop_iterations: None,
+      float_precision: None,
+      undefined_read: UndefinedRead::Error,
+      string_plus_int: StringPlusInt::Error,
+      float_policy: FloatPolicy::Ieee,
+      integral_float_indices: false,
+    }
+  }
+
+  pub fn set_strict(&mut self, strict: bool) {
+    self.strict = strict;
+  }
+
+  pub fn set_undefined_read(&mut self, undefined_read: UndefinedRead) {
+    self.undefined_read = undefined_read;
+  }
+
+  pub fn set_string_plus_int(&mut self, string_plus_int: StringPlusInt) {
+    self.string_plus_int = string_plus_int;
+  }
+
+  pub fn set_float_policy(&mut self, float_policy: FloatPolicy) {
+    self.float_policy = float_policy;
+  }
+
+  // Lets a Float with no fractional part, such as `2.0`, index an array as the
+  // Integer it equals. Other Floats are still InvalidIndexType.
+  pub fn set_integral_float_indices(&mut self, integral_float_indices: bool) {
+    self.integral_float_indices = integral_float_indices;
+  }
+
+  // Variable and builtin names ignore case, so `PRINT(X)` calls Print with x.
+  // Pair it with Lexer::set_case_insensitive_keywords for BASIC-like dialects.
+  pub fn set_case_insensitive(&mut self, case_insensitive: bool) {
+    self.case_insensitive = case_insensitive;
+  }
+
+  // A disabled builtin is an unknown method to the program and is left out
+  // of Methods(). Names that are not builtins are ignored.
+  pub fn set_method_enabled(&mut self, name: &str, enabled: bool) {
+    if let Some(name) = METHODS.iter().find(|m| m.eq_ignore_ascii_case(name)) {
+      if enabled {
+        self.disabled_methods.remove(name);
+      } else {
+        self.disabled_methods.insert(name);
+      }
+    }
+  }
+
+  pub fn set_timer(&mut self, timer: Box<dyn Timer>) {
+    self.timer = Some(Rc::new(RefCell::new(timer)));
+  }
+
+  pub fn set_clock(&mut self, clock: Box<dyn Clock>) {
+    self.clock = Some(Rc::new(RefCell::new(clock)));
+  }
+
+  // Records how long each top-level statement took; needs a Clock.
+  pub fn set_profiling(&mut self, profiling: bool) {
+    self.profiling = profiling;
+  }
+
+  // (statement index, duration) pairs from the last execute().
+  pub fn statement_timings(&self) -> &[(usize, Duration)] {
+    &self.statement_timings
+  }
+
+  // Statements are numbered in preorder over the whole program, nested blocks
+  // included, starting from 0.
+  pub fn set_coverage(&mut self, coverage: bool) {
+    self.coverage = coverage;
+  }
+
+  pub fn covered_statements(&self) -> &BTreeSet<usize> {
+    &self.covered_statements
+  }
+
+  // The preorder number of the first statement of `statement`'s `block`th
+  // nested block, given the number of `statement` itself.
+  fn block_start(&self, index: usize, statement: &Statement, block: usize) -> usize {
+    if !self.coverage {
+      return 0;
+    }
+    let skipped: usize = statement.nested_blocks()[..block]
+      .iter()
+      .flat_map(|b| b.iter())
+      .map(Statement::statement_count)
+      .sum();
+    index + 1 + skipped
+  }
+
+  #[cfg(not(feature = "wasm"))]
+  fn default_clock() -> Option<SharedClock> {
+    Some(Rc::new(RefCell::new(Box::new(InstantClock::new()))))
+  }
+
+  #[cfg(feature = "wasm")]
+  fn default_clock() -> Option<SharedClock> {
+    None
+  }
+
+  #[cfg(not(feature = "wasm"))]
+  fn default_timer() -> Option<SharedTimer> {
+    Some(Rc::new(RefCell::new(Box::new(ThreadTimer))))
+  }
+
+  #[cfg(feature = "wasm")]
+  fn default_timer() -> Option<SharedTimer> {
+    None
+  }
+
+  #[cfg(not(feature = "wasm"))]
+  fn default_output() -> Box<dyn Write> {
+    Box::new(io::stdout())
+  }
+
+  #[cfg(feature = "wasm")]
+  fn default_output() -> Box<dyn Write> {
+    Box::new(io::sink())
+  }
+
+  #[cfg(not(feature = "wasm"))]
+  fn default_error_output() -> Box<dyn Write> {
+    Box::new(io::stderr())
+  }
+
+  #[cfg(feature = "wasm")]
+  fn default_error_output() -> Box<dyn Write> {
+    Box::new(io::sink())
   }
+
+  pub fn set_output(&mut self, output: Box<dyn Write>) {
+    self.output = output;
+  }
+
+  // Destination of EPrint, stderr by default.
+  pub fn set_error_output(&mut self, error_output: Box<dyn Write>) {
+    self.error_output = error_output;
+  }
+
+  // When buffered, Print output is held in memory until Flush() is called or
+  // the program finishes.
+  pub fn set_buffered(&mut self, buffered: bool) {
+    self.buffered = buffered;
+  }
+
+  // Flushes the output after every Print so it stays ordered with other
+  // writers of the host; otherwise flushing is left to the writer itself.
+  pub fn set_flush_each_print(&mut self, flush_each_print: bool) {
+    self.flush_each_print = flush_each_print;
+  }
+
+  // Checked against estimated_memory() after every assignment made by the program.
+  pub fn set_memory_limit(&mut self, limit: Option<usize>) {
+    self.memory_limit = limit;
+  }
+
+  // Caps the iterations of each individual loop, not of the whole program.
+  pub fn set_max_loop_iterations(&mut self, limit: Option<usize>) {
+    self.max_loop_iterations = limit;
+  }
+
+  // Decimal places for Floats given directly to Print and EPrint; None prints
+  // the shortest text that reads back as the same number.
+  pub fn set_float_precision(&mut self, precision: Option<usize>) {
+    self.float_precision = precision;
+  }
+
+  // The callback receives the old value (Undefined if unset) and the new value
+  // every time the variable is assigned, and Undefined as the new value when
+  // Unset removes it.
+  pub fn watch(&mut self, name: &str, callback: WatchCallback) -> WatchHandle {
+    let handle = WatchHandle(self.next_watch_id);
+    self.next_watch_id += 1;
+    let key = self.variable_key(name);
+    self.watchers.push((handle, key, callback));
+    handle
+  }
+
+  pub fn unwatch(&mut self, handle: WatchHandle) {
+    self.watchers.retain(|(h, _, _)| *h != handle);
+  }
+
   pub fn execute(&mut self, program: &Program) -> Result<Object, RuntimeError> {
-    let mut r = Object::Undefined;
-    for s in program.statements.iter() {
+    let r = self.execute_program(program);
+    let flushed = self.flush_output();
+    let r = r?;
+    flushed?;
+    Ok(r)
+  }
+
+  // Seeds the environment before running; seeded values replace existing ones.
+  // Seeding goes through the same checks as assignment, so a Const cannot be
+  // replaced, strict mode keeps types and the memory limit applies.
+  pub fn execute_with_env(
+    &mut self,
+    program: &Program,
+    env: BTreeMap<String, Object>,
+  ) -> Result<Object, RuntimeError> {
+    let depth = self.scopes.len() - 1;
+    for (name, value) in env {
+      self.store_checked(depth, &name, &value)?;
+    }
+    self.execute(program)
+  }
+
+  // Runs `program` on a copy of the current variables and discards the copy
+  // afterwards, so this Executor is left as it was. Arrays, Heaps and Sets are
+  // shared with the copy until it changes them. The copy has the same settings
+  // and uses the same Timer and Clock, but output goes nowhere, watchers are
+  // not called and neither timings nor coverage are recorded.
+  pub fn execute_readonly(&self, program: &Program) -> Result<Object, RuntimeError> {
+    let mut overlay = Executor {
+      scopes: self.scopes.clone(),
+      constants: self.constants.clone(),
+      strict: self.strict,
+      case_insensitive: self.case_insensitive,
+      exiting_do: false,
+      disabled_methods: self.disabled_methods.clone(),
+      timer: self.timer.clone(),
+      clock: self.clock.clone(),
+      profiling: false,
+      statement_timings: vec![],
+      coverage: false,
+      covered_statements: BTreeSet::new(),
+      statement_index: 0,
+      watchers: vec![],
+      next_watch_id: 0,
+      output: Box::new(io::sink()),
+      error_output: Box::new(io::sink()),
+      buffered: false,
+      flush_each_print: false,
+      output_buffer: vec![],
+      memory_limit: self.memory_limit,
+      max_loop_iterations: self.max_loop_iterations,
+      float_precision: self.float_precision,
+      undefined_read: self.undefined_read,
+      string_plus_int: self.string_plus_int,
+      float_policy: self.float_policy,
+      integral_float_indices: self.integral_float_indices,
+    };
+    overlay.execute(program)
+  }
+
+  fn execute_program(&mut self, program: &Program) -> Result<Object, RuntimeError> {
+    self.statement_timings.clear();
+    self.covered_statements.clear();
+    let mut r = Object::Unit;
+    let mut index = 0;
+    for (i, s) in program.statements.iter().enumerate() {
+      let started = self.clock_now();
+      self.statement_index = index;
       r = self.execute_statement(s)?;
+      if self.coverage {
+        index += s.statement_count();
+      }
+      if let (Some(started), Some(finished)) = (started, self.clock_now()) {
+        self
+          .statement_timings
+          .push((i, finished.saturating_sub(started)));
+      }
       debug!("Statement: {}", r);
     }
     Ok(r)
   }
 
+  fn clock_now(&mut self) -> Option<Duration> {
+    if !self.profiling {
+      return None;
+    }
+    self.clock.as_ref().map(|clock| clock.borrow_mut().now())
+  }
+
+  fn write_output(&mut self, bytes: &[u8]) -> Result<(), RuntimeError> {
+    if self.buffered {
+      self.output_buffer.extend_from_slice(bytes);
+      return Ok(());
+    }
+    self
+      .output
+      .write_all(bytes)
+      .map_err(|e| RuntimeError::OutputError(e.to_string()))
+  }
+
+  fn flush_output(&mut self) -> Result<(), RuntimeError> {
+    if !self.output_buffer.is_empty() {
+      let pending = std::mem::take(&mut self.output_buffer);
+      self
+        .output
+        .write_all(&pending)
+        .map_err(|e| RuntimeError::OutputError(e.to_string()))?;
+    }
+    self
+      .output
+      .flush()
+      .map_err(|e| RuntimeError::OutputError(e.to_string()))
+  }
+
+  // Declares the variable in the current scope.
   pub fn set_variable(&mut self, name: String, value: &Object) {
+    let depth = self.scopes.len() - 1;
+    self.store_variable(depth, &name, value);
+  }
+
+  fn store_variable(&mut self, depth: usize, name: &str, value: &Object) {
     debug!("set_variable: {}={}", name, value);
-    self.variables.insert(name, value.clone());
+    let key = self.variable_key(name);
+    let old = self.scopes[depth].insert(key.clone(), value.clone());
+    if !self.watchers.is_empty() {
+      self.notify_watchers(&key, &old.unwrap_or(Object::Undefined), value);
+    }
+  }
+
+  fn notify_watchers(&mut self, key: &str, old: &Object, new: &Object) {
+    for (_, watched, callback) in self.watchers.iter_mut() {
+      if watched == key {
+        callback(old, new);
+      }
+    }
+  }
+
+  // Changes a variable where it is stored instead of reading a copy and
+  // assigning it back, so an unshared Heap or Set is not cloned first. The
+  // old value is only kept when the variable is being watched.
+  fn update_variable<T>(
+    &mut self,
+    name: &str,
+    update: impl FnOnce(&mut Object) -> Result<T, RuntimeError>,
+  ) -> Result<T, RuntimeError> {
+    self.ensure_mutable(name)?;
+    let key = self.variable_key(name);
+    let watched = self.watchers.iter().any(|(_, watched, _)| *watched == key);
+    let old = if watched {
+      self.get_variable(name)
+    } else {
+      None
+    };
+    let value = self
+      .variable_mut(name)
+      .ok_or_else(|| RuntimeError::UndefinedVariable(name.to_string()))?;
+    let result = update(value)?;
+    if let Some(old) = old {
+      let new = value.clone();
+      self.notify_watchers(&key, &old, &new);
+    }
+    self.check_memory_limit()?;
+    Ok(result)
+  }
+
+  // Updates the variable in the innermost scope that has it, or declares it
+  // in the current scope.
+  fn assign_variable(&mut self, name: &str, value: &Object) -> Result<(), RuntimeError> {
+    let key = self.variable_key(name);
+    let depth = self.scope_of(&key).unwrap_or_else(|| self.scopes.len() - 1);
+    self.store_checked(depth, name, value)
+  }
+
+  // `Dim` and `Const` always bind in the current scope, shadowing outer ones.
+  fn declare_variable(
+    &mut self,
+    name: &str,
+    value: &Object,
+    constant: bool,
+  ) -> Result<(), RuntimeError> {
+    let depth = self.scopes.len() - 1;
+    let key = self.variable_key(name);
+    let redeclared = self.scopes[depth].contains_key(&key);
+    // Redeclaring a constant is reported by store_checked as AssignToConst.
+    if self.strict && redeclared && !self.constants[depth].contains(&key) {
+      return Err(RuntimeError::AlreadyDeclared(name.to_string()));
+    }
+    self.store_checked(depth, name, value)?;
+    if constant {
+      self.constants[depth].insert(key);
+    }
+    Ok(())
+  }
+
+  fn store_checked(
+    &mut self,
+    depth: usize,
+    name: &str,
+    value: &Object,
+  ) -> Result<(), RuntimeError> {
+    if self.constants[depth].contains(&self.variable_key(name)) {
+      return Err(RuntimeError::AssignToConst(name.to_string()));
+    }
+    if self.strict {
+      if let Some(current) = self.scopes[depth].get(&self.variable_key(name)) {
+        let expected = current.type_of();
+        let actual = value.type_of();
+        if expected != RuntimeType::Undefined && expected != actual {
+          return Err(RuntimeError::TypeMismatch { expected, actual });
+        }
+      }
+    }
+    self.store_variable(depth, name, value);
+    self.check_memory_limit()
+  }
+
+  fn bind_variable(
+    &mut self,
+    name: &str,
+    value: &Object,
+    binding: Binding,
+  ) -> Result<(), RuntimeError> {
+    match binding {
+      Binding::Assign if self.scope_of(&self.variable_key(name)).is_none() => {
+        Err(RuntimeError::UndefinedVariable(name.to_string()))
+      }
+      Binding::Assign => self.assign_variable(name, value),
+      Binding::Dim => self.declare_variable(name, value, false),
+      Binding::Const => self.declare_variable(name, value, true),
+    }
+  }
+
+  fn declaration_binding(constant: bool) -> Binding {
+    if constant {
+      Binding::Const
+    } else {
+      Binding::Dim
+    }
+  }
+
+  // For methods that change a variable in place or remove it.
+  fn ensure_mutable(&self, name: &str) -> Result<(), RuntimeError> {
+    let key = self.variable_key(name);
+    match self.scope_of(&key) {
+      Some(depth) if self.constants[depth].contains(&key) => {
+        Err(RuntimeError::AssignToConst(name.to_string()))
+      }
+      _ => Ok(()),
+    }
+  }
+
+  fn scope_of(&self, key: &str) -> Option<usize> {
+    self
+      .scopes
+      .iter()
+      .rposition(|scope| scope.contains_key(key))
+  }
+
+  fn variable_mut(&mut self, name: &str) -> Option<&mut Object> {
+    let key = self.variable_key(name);
+    let depth = self.scope_of(&key)?;
+    self.scopes[depth].get_mut(&key)
+  }
+
+  fn check_memory_limit(&self) -> Result<(), RuntimeError> {
+    if let Some(limit) = self.memory_limit {
+      let used = self.estimated_memory();
+      if used > limit {
+        return Err(RuntimeError::MemoryLimitExceeded { limit, used });
+      }
+    }
+    Ok(())
   }
 
   pub fn get_variable(&mut self, name: &str) -> Option<Object> {
-    match self.variables.get(name) {
+    let key = self.variable_key(name);
+    match self
+      .scope_of(&key)
+      .and_then(|depth| self.scopes[depth].get(&key))
+    {
       Some(value) => {
         debug!("get_variable: {}: {}", name, value);
         Some(value.clone())
@@ -43,35 +639,172 @@ impl Executor {
     }
   }
 
-  fn execute_statements(&mut self, statements: &Vec<Statement>) -> Result<Object, RuntimeError> {
+  pub fn estimated_memory(&self) -> usize {
+    self
+      .scopes
+      .iter()
+      .flatten()
+      .map(|(name, value)| name.capacity() + std::mem::size_of::<Object>() + value.heap_size())
+      .sum()
+  }
+
+  fn variable_key(&self, name: &str) -> String {
+    if self.case_insensitive {
+      name.to_lowercase()
+    } else {
+      name.to_string()
+    }
+  }
+
+  pub fn variable_names(&self) -> Vec<String> {
+    let names: BTreeSet<&String> = self.scopes.iter().flat_map(|scope| scope.keys()).collect();
+    names.into_iter().cloned().collect()
+  }
+
+  // Variables are kept sorted by name, so the hash does not depend on the order
+  // in which they were assigned.
+  pub fn state_hash(&self) -> u64 {
+    let mut state = vec![];
+    for (name, value) in self.scopes.iter().flatten() {
+      Self::encode_text(name, &mut state);
+      Self::encode_value(value, &mut state);
+    }
+    builtins::fnv1a(&state)
+  }
+
+  // Values that compare equal are encoded the same way: Heaps by their sorted
+  // contents and both zero Floats as 0.0.
+  fn encode_value(value: &Object, state: &mut Vec<u8>) {
+    match value {
+      Object::Undefined => state.push(0),
+      Object::Unit => state.push(1),
+      Object::Integer(n) => {
+        state.push(2);
+        state.extend_from_slice(&n.to_le_bytes());
+      }
+      Object::Float(n) => {
+        state.push(3);
+        let n = if *n == 0.0 { 0.0 } else { *n };
+        state.extend_from_slice(&n.to_bits().to_le_bytes());
+      }
+      Object::Boolean(b) => state.extend_from_slice(&[4, *b as u8]),
+      Object::String(s) => {
+        state.push(5);
+        Self::encode_text(s, state);
+      }
+      Object::Array(elements) => {
+        state.push(6);
+        state.extend_from_slice(&elements.len().to_le_bytes());
+        for e in elements.iter() {
+          Self::encode_value(e, state);
+        }
+      }
+      Object::Heap(heap) => {
+        state.push(7);
+        state.extend_from_slice(&heap.len().to_le_bytes());
+        for n in heap.sorted() {
+          state.extend_from_slice(&n.to_le_bytes());
+        }
+      }
+      Object::Set(keys) => {
+        state.push(8);
+        state.extend_from_slice(&keys.len().to_le_bytes());
+        for k in keys.iter() {
+          Self::encode_value(&k.to_object(), state);
+        }
+      }
+      Object::StringBuilder(s) => {
+        state.push(9);
+        Self::encode_text(s, state);
+      }
+    }
+  }
+
+  fn encode_text(text: &str, state: &mut Vec<u8>) {
+    state.extend_from_slice(&text.len().to_le_bytes());
+    state.extend_from_slice(text.as_bytes());
+  }
+
+  // Each block runs in a scope of its own.
+  // `first` is the preorder number of the first statement, for coverage.
+  fn execute_statements(
+    &mut self,
+    statements: &[Statement],
+    first: usize,
+  ) -> Result<Object, RuntimeError> {
+    self.scopes.push(BTreeMap::new());
+    self.constants.push(BTreeSet::new());
+    let r = self.execute_block(statements, first);
+    self.scopes.pop();
+    self.constants.pop();
+    r
+  }
+
+  fn execute_block(
+    &mut self,
+    statements: &[Statement],
+    first: usize,
+  ) -> Result<Object, RuntimeError> {
+    let mut index = first;
     for s in statements {
+      self.statement_index = index;
       self.execute_statement(s)?;
+      if self.coverage {
+        index += s.statement_count();
+      }
+      if self.exiting_do {
+        break;
+      }
     }
-    Ok(Object::Undefined)
+    Ok(Object::Unit)
   }
 
   fn execute_statement(&mut self, statement: &Statement) -> Result<Object, RuntimeError> {
+    let index = self.statement_index;
+    if self.coverage {
+      self.covered_statements.insert(index);
+    }
     match statement {
       Statement::Declaration {
         identifier,
         expression,
-      } => self.execute_const_assignment(identifier.to_string(), &expression),
+        constant,
+      } => self.execute_const_assignment(
+        identifier.to_string(),
+        expression,
+        Self::declaration_binding(*constant),
+      ),
       Statement::Assignment {
         identifier,
         expression,
-      } => self.execute_const_assignment(identifier.to_string(), &expression),
+      } => self.execute_const_assignment(identifier.to_string(), expression, Binding::Assign),
+      Statement::MultipleDeclaration {
+        identifiers,
+        expressions,
+        constant,
+      } => self.execute_multiple_assignment(
+        identifiers,
+        expressions,
+        Self::declaration_binding(*constant),
+      ),
+      Statement::MultipleAssignment {
+        identifiers,
+        expressions,
+      } => self.execute_multiple_assignment(identifiers, expressions, Binding::Assign),
       Statement::MethodInvocation {
         identifier,
         arguments,
-      } => self.execute_method(identifier, &arguments),
+      } => self.execute_method(identifier, arguments),
       Statement::ForStatement {
         loop_counter,
         loop_counter_from,
         loop_counter_to,
         block,
       } => {
+        let first = self.block_start(index, statement, 0);
         let mut counter = self.execute_expression(loop_counter_from)?;
-        self.set_variable(loop_counter.to_owned(), &counter);
+        self.assign_variable(loop_counter, &counter)?;
+        let mut iterations = 0;
         loop {
           let to_value = self.execute_expression(loop_counter_to)?;
           let exit = match (counter, to_value) {
@@ -86,29 +819,35 @@ impl Executor {
           if exit {
             break;
           }
-          self.execute_statements(block)?;
+          self.count_iteration(&mut iterations)?;
+          self.execute_statements(block, first)?;
+          if self.exiting_do {
+            break;
+          }
 
           counter = match self.get_variable(loop_counter) {
             Some(v) => v,
             None => return Err(RuntimeError::UndefinedVariable(loop_counter.to_string())),
           };
+          // No Integer limit can exceed i32::MAX, so the loop is over.
+          if let Object::Integer(i32::MAX) = counter {
+            break;
+          }
           counter = counter.add(1)?;
-          self.set_variable(loop_counter.to_owned(), &counter);
+          self.assign_variable(loop_counter, &counter)?;
         }
-        return Ok(Object::Undefined);
+        Ok(Object::Unit)
       }
       Statement::IfStatement {
         if_blocks,
         else_statements,
       } => {
-        for (c, b) in if_blocks {
+        for (k, (c, b)) in if_blocks.iter().enumerate() {
           let e = self.execute_expression(c)?;
           match e {
             Object::Boolean(true) => {
-              for s in b {
-                self.execute_statement(s)?;
-              }
-              return Ok(Object::Undefined);
+              let first = self.block_start(index, statement, k);
+              return self.execute_statements(b, first);
             }
             Object::Boolean(false) => {}
             a => {
@@ -119,98 +858,713 @@ impl Executor {
             }
           }
         }
-        for s in else_statements {
-          self.execute_statement(s)?;
+        let first = self.block_start(index, statement, if_blocks.len());
+        self.execute_statements(else_statements, first)
+      }
+      Statement::DoLoop { block } => {
+        let first = self.block_start(index, statement, 0);
+        let mut iterations = 0;
+        loop {
+          self.count_iteration(&mut iterations)?;
+          self.execute_statements(block, first)?;
+          if self.exiting_do {
+            self.exiting_do = false;
+            break;
+          }
+        }
+        Ok(Object::Unit)
+      }
+      Statement::GuardStatement {
+        condition,
+        else_statements,
+      } => match self.execute_expression(condition)? {
+        Object::Boolean(true) => Ok(Object::Unit),
+        Object::Boolean(false) => {
+          let first = self.block_start(index, statement, 0);
+          self.execute_statements(else_statements, first)
+        }
+        a => Err(RuntimeError::TypeMismatch {
+          expected: RuntimeType::Boolean,
+          actual: a.type_of(),
+        }),
+      },
+      Statement::WhileStatement { condition, block } => {
+        let first = self.block_start(index, statement, 0);
+        let mut iterations = 0;
+        loop {
+          match self.execute_expression(condition)? {
+            Object::Boolean(true) => {}
+            Object::Boolean(false) => break,
+            a => {
+              return Err(RuntimeError::TypeMismatch {
+                expected: RuntimeType::Boolean,
+                actual: a.type_of(),
+              })
+            }
+          }
+          self.count_iteration(&mut iterations)?;
+          self.execute_statements(block, first)?;
+          // Exit Do leaves the enclosing Do loop as well.
+          if self.exiting_do {
+            break;
+          }
         }
-        return Ok(Object::Undefined);
+        Ok(Object::Unit)
       }
-      Statement::Empty => Ok(Object::Undefined),
+      Statement::ExitDo => {
+        self.exiting_do = true;
+        Ok(Object::Unit)
+      }
+      Statement::Empty => Ok(Object::Unit),
+    }
+  }
+
+  // Strings are printed as their text; other values as they are displayed.
+  fn print_text(&self, value: &Object) -> String {
+    match (value, self.float_precision) {
+      (Object::String(s), _) => s.clone(),
+      (Object::Float(n), Some(precision)) => format!("Float({:.*})", precision, n),
+      (v, _) => v.to_string(),
+    }
+  }
+
+  fn count_iteration(&self, iterations: &mut usize) -> Result<(), RuntimeError> {
+    *iterations += 1;
+    match self.max_loop_iterations {
+      Some(limit) if *iterations > limit => Err(RuntimeError::LoopLimitExceeded(limit)),
+      _ => Ok(()),
     }
   }
 
   fn execute_method(
     &mut self,
     identifier: &str,
-    arguments: &Vec<Expression>,
+    arguments: &[Expression],
   ) -> Result<Object, RuntimeError> {
+    let identifier = if self.case_insensitive {
+      Self::builtin_spelling(identifier)
+    } else {
+      identifier
+    };
+    if self.disabled_methods.contains(identifier) {
+      return Err(RuntimeError::UnknownMethod(identifier.to_string()));
+    }
     match identifier {
       "Print" => {
-        for a in arguments {
-          let evaluated = self.execute_expression(a)?;
-          println!("{}", evaluated);
+        for evaluated in self.evaluate_elements(arguments)? {
+          self.write_output(format!("{}\n", self.print_text(&evaluated)).as_bytes())?;
+        }
+        if self.flush_each_print {
+          self.flush_output()?;
         }
-        Ok(Object::Undefined)
+        Ok(Object::Unit)
       }
-      _ => Err(RuntimeError::UnknownMethod(identifier.to_string())),
-    }
-  }
-
-  fn execute_const_assignment(
-    &mut self,
-    identifier: String,
-    expression: &Expression,
-  ) -> Result<Object, RuntimeError> {
-    let evaluated = self.execute_expression(expression)?;
-    self.set_variable(identifier.to_owned(), &evaluated);
-    Ok(evaluated)
-  }
-
-  fn execute_expression(&mut self, expression: &Expression) -> Result<Object, RuntimeError> {
-    match expression {
-      Expression::Identifier(name) => match self.get_variable(name) {
-        Some(value) => Ok(value),
-        _ => Ok(Object::Undefined),
-      },
-      Expression::Integer(value) => Ok(Object::Integer(*value)),
-      Expression::String(value) => Ok(Object::String(value.clone())),
-      Expression::Binary {
-        left,
-        operator,
-        right,
-      } => {
-        let l = self.execute_expression(&left)?;
-        let r = self.execute_expression(&right)?;
-        match (l, r) {
-          (Object::Integer(l), Object::Integer(r)) => match operator {
-            BinaryOperator::ADD => Ok(Object::Integer(l + r)),
-            BinaryOperator::SUB => Ok(Object::Integer(l - r)),
-            BinaryOperator::MUL => Ok(Object::Integer(l * r)),
-            BinaryOperator::DIV => Ok(Object::Integer(l / r)),
-            BinaryOperator::MOD => Ok(Object::Integer(l % r)),
-            BinaryOperator::EQ => Ok(Object::Boolean(l == r)),
-            BinaryOperator::NE => Ok(Object::Boolean(l != r)),
-            BinaryOperator::GT => Ok(Object::Boolean(l > r)),
-            BinaryOperator::LT => Ok(Object::Boolean(l < r)),
-            BinaryOperator::LE => Ok(Object::Boolean(l >= r)),
-            BinaryOperator::GE => Ok(Object::Boolean(l <= r)),
-            _ => Err(RuntimeError::TypeMismatch {
-              expected: RuntimeType::Integer,
-              actual: RuntimeType::Boolean,
-            }),
-          },
-          (Object::Boolean(l), Object::Boolean(r)) => match operator {
-            BinaryOperator::AND => Ok(Object::Boolean(l && r)),
-            BinaryOperator::XOR => Ok(Object::Boolean(l || r)),
-            BinaryOperator::OR => Ok(Object::Boolean(l || r)),
-            _ => Err(RuntimeError::TypeMismatch {
-              expected: RuntimeType::Boolean,
-              actual: RuntimeType::Integer,
-            }),
-          },
-          (l, r) => Err(RuntimeError::TypeMismatch {
-            expected: l.type_of(),
-            actual: r.type_of(),
-          }),
+      "Debug" => {
+        let values = self.evaluate_arguments(identifier, arguments, 1)?;
+        self.write_output(format!("{:?}\n", values[0]).as_bytes())?;
+        Ok(Object::Unit)
+      }
+      "EPrint" => {
+        for evaluated in self.evaluate_elements(arguments)? {
+          self
+            .error_output
+            .write_all(format!("{}\n", self.print_text(&evaluated)).as_bytes())
+            .map_err(|e| RuntimeError::OutputError(e.to_string()))?;
         }
+        Ok(Object::Unit)
       }
-      Expression::Unary {
-        operator,
-        expression,
-      } => {
-        let evaluated = self.execute_expression(&expression)?;
+      "Flush" => {
+        self.evaluate_arguments(identifier, arguments, 0)?;
+        self.flush_output()?;
+        Ok(Object::Unit)
+      }
+      "Unset" => {
+        let name = Self::variable_argument(identifier, arguments)?;
+        self.ensure_mutable(&name)?;
+        let key = self.variable_key(&name);
+        let removed = match self.scope_of(&key) {
+          Some(depth) => self.scopes[depth].remove(&key),
+          None => None,
+        };
+        match removed {
+          Some(old) => self.notify_watchers(&key, &old, &Object::Undefined),
+          None if self.strict => return Err(RuntimeError::UndefinedVariable(name)),
+          None => {}
+        }
+        Ok(Object::Unit)
+      }
+      "Inc" => {
+        let name = Self::variable_argument(identifier, arguments)?;
+        self.step_variable(&name, BinaryOperator::ADD)
+      }
+      "Dec" => {
+        let name = Self::variable_argument(identifier, arguments)?;
+        self.step_variable(&name, BinaryOperator::SUB)
+      }
+      "Halt" => {
+        let values = self.evaluate_arguments(identifier, arguments, 1)?;
+        match values[0] {
+          Object::Integer(code) => Err(RuntimeError::Halt(code)),
+          ref v => Err(RuntimeError::TypeMismatch {
+            expected: RuntimeType::Integer,
+            actual: v.type_of(),
+          }),
+        }
+      }
+      "Heap" => {
+        self.evaluate_arguments(identifier, arguments, 0)?;
+        Ok(Object::Heap(Heap::default()))
+      }
+      "HeapPush" => {
+        let (name, value) = self.variable_and_value(identifier, arguments)?;
+        let n = match value {
+          Object::Integer(n) => n,
+          v => {
+            return Err(RuntimeError::TypeMismatch {
+              expected: RuntimeType::Integer,
+              actual: v.type_of(),
+            })
+          }
+        };
+        self.update_variable(&name, |v| {
+          Self::heap_mut(v)?.push(n);
+          Ok(())
+        })?;
+        Ok(Object::Unit)
+      }
+      "HeapPop" => {
+        let name = Self::variable_argument(identifier, arguments)?;
+        let popped = self.update_variable(&name, |v| Ok(Self::heap_mut(v)?.pop()))?;
+        self.heap_value(identifier, popped)
+      }
+      "HeapPeek" => {
+        let name = Self::variable_argument(identifier, arguments)?;
+        let peeked = self.heap_variable(&name)?.peek();
+        self.heap_value(identifier, peeked)
+      }
+      "Set" => {
+        self.evaluate_arguments(identifier, arguments, 0)?;
+        Ok(Object::Set(Rc::default()))
+      }
+      "SetAdd" => {
+        let (name, value) = self.variable_and_value(identifier, arguments)?;
+        let key = SetKey::from_object(&value)?;
+        self.update_variable(&name, |v| Ok(Self::set_keys_mut(v)?.insert(key)))?;
+        Ok(Object::Unit)
+      }
+      "SetHas" => {
+        let (name, value) = self.variable_and_value(identifier, arguments)?;
+        let keys = self.set_variable_keys(&name)?;
+        Ok(Object::Boolean(
+          keys.contains(&SetKey::from_object(&value)?),
+        ))
+      }
+      "SetRemove" => {
+        let (name, value) = self.variable_and_value(identifier, arguments)?;
+        let key = SetKey::from_object(&value)?;
+        let removed = self.update_variable(&name, |v| Ok(Self::set_keys_mut(v)?.remove(&key)))?;
+        Ok(Object::Boolean(removed))
+      }
+      "StringBuilder" => {
+        self.evaluate_arguments(identifier, arguments, 0)?;
+        Ok(Object::StringBuilder(String::new()))
+      }
+      // Appends in place rather than reassigning, so building a long string
+      // stays linear; watchers are not notified.
+      "Append" => {
+        let (name, value) = self.variable_and_value(identifier, arguments)?;
+        let text = match value {
+          Object::String(s) => s,
+          v => {
+            return Err(RuntimeError::TypeMismatch {
+              expected: RuntimeType::String,
+              actual: v.type_of(),
+            })
+          }
+        };
+        self.ensure_mutable(&name)?;
+        match self.variable_mut(&name) {
+          Some(Object::StringBuilder(s)) => s.push_str(&text),
+          Some(v) => {
+            return Err(RuntimeError::TypeMismatch {
+              expected: RuntimeType::StringBuilder,
+              actual: v.type_of(),
+            })
+          }
+          None => return Err(RuntimeError::UndefinedVariable(name)),
+        }
+        self.check_memory_limit()?;
+        Ok(Object::Unit)
+      }
+      "Build" => {
+        let values = self.evaluate_arguments(identifier, arguments, 1)?;
+        match &values[0] {
+          Object::StringBuilder(s) => Ok(Object::String(s.clone())),
+          v => Err(RuntimeError::TypeMismatch {
+            expected: RuntimeType::StringBuilder,
+            actual: v.type_of(),
+          }),
+        }
+      }
+      "Len" => {
+        let values = self.evaluate_arguments(identifier, arguments, 1)?;
+        let len = match &values[0] {
+          Object::String(s) | Object::StringBuilder(s) => s.chars().count(),
+          Object::Array(elements) => elements.len(),
+          Object::Heap(heap) => heap.len(),
+          Object::Set(keys) => keys.len(),
+          v => {
+            return Err(RuntimeError::InvalidArgument(format!(
+              "Len expects a String, Array, Heap or Set, but {}",
+              v.type_of()
+            )))
+          }
+        };
+        i32::try_from(len)
+          .map(Object::Integer)
+          .map_err(|_| RuntimeError::InvalidArgument(format!("Len {} is too large", len)))
+      }
+      "Version" => {
+        self.evaluate_arguments(identifier, arguments, 0)?;
+        Ok(Object::String(env!("CARGO_PKG_VERSION").to_string()))
+      }
+      "Methods" => {
+        self.evaluate_arguments(identifier, arguments, 0)?;
+        let names = METHODS
+          .iter()
+          .filter(|name| self.method_available(name))
+          .map(|name| Object::String(name.to_string()))
+          .collect();
+        Ok(Object::Array(Rc::new(names)))
+      }
+      "AssertEq" => {
+        let mut values = self.evaluate_arguments(identifier, arguments, 2)?;
+        let expected = values.pop().unwrap();
+        let actual = values.pop().unwrap();
+        if actual != expected {
+          return Err(RuntimeError::AssertionFailed { actual, expected });
+        }
+        Ok(Object::Unit)
+      }
+      // Only the chosen branch is evaluated.
+      "If" => {
+        if arguments.len() != 3 {
+          return Err(RuntimeError::WrongNumberOfArguments {
+            method: identifier.to_string(),
+            expected: 3,
+            actual: arguments.len(),
+          });
+        }
+        match self.execute_expression(&arguments[0])? {
+          Object::Boolean(true) => self.execute_expression(&arguments[1]),
+          Object::Boolean(false) => self.execute_expression(&arguments[2]),
+          a => Err(RuntimeError::TypeMismatch {
+            expected: RuntimeType::Boolean,
+            actual: a.type_of(),
+          }),
+        }
+      }
+      "TypeOf" => {
+        let values = self.evaluate_arguments(identifier, arguments, 1)?;
+        Ok(Object::String(values[0].type_of().to_string()))
+      }
+      "Truthy" => {
+        let values = self.evaluate_arguments(identifier, arguments, 1)?;
+        Ok(Object::Boolean(values[0].truthy()))
+      }
+      // Without a Timer (the wasm default) Sleep is an unknown method.
+      "Sleep" if self.timer.is_some() => {
+        let values = self.evaluate_arguments(identifier, arguments, 1)?;
+        match values[0] {
+          Object::Integer(millis) if 0 <= millis => {
+            if let Some(timer) = &self.timer {
+              timer
+                .borrow_mut()
+                .sleep(Duration::from_millis(millis as u64));
+            }
+            Ok(Object::Unit)
+          }
+          Object::Integer(millis) => Err(RuntimeError::InvalidArgument(format!(
+            "Sleep duration must not be negative, but {}",
+            millis
+          ))),
+          ref v => Err(RuntimeError::TypeMismatch {
+            expected: RuntimeType::Integer,
+            actual: v.type_of(),
+          }),
+        }
+      }
+      "Base64Encode" => {
+        let values = self.evaluate_arguments(identifier, arguments, 1)?;
+        builtins::base64_encode(&values[0])
+      }
+      "Base64Decode" => {
+        let values = self.evaluate_arguments(identifier, arguments, 1)?;
+        builtins::base64_decode(&values[0])
+      }
+      "Hash" => {
+        let values = self.evaluate_arguments(identifier, arguments, 1)?;
+        builtins::hash(&values[0])
+      }
+      "Sign" => {
+        let values = self.evaluate_arguments(identifier, arguments, 1)?;
+        builtins::sign(&values[0])
+      }
+      "DivMod" => {
+        let values = self.evaluate_arguments(identifier, arguments, 2)?;
+        builtins::div_mod(&values[0], &values[1])
+      }
+      "Abs" => {
+        let values = self.evaluate_arguments(identifier, arguments, 1)?;
+        builtins::abs(&values[0])
+      }
+      "JsonParse" => {
+        let values = self.evaluate_arguments(identifier, arguments, 1)?;
+        builtins::json_parse(&values[0])
+      }
+      "JsonStringify" => {
+        let values = self.evaluate_arguments(identifier, arguments, 1)?;
+        builtins::json_stringify(&values[0])
+      }
+      "CsvParse" => {
+        let values = self.evaluate_arguments(identifier, arguments, 1)?;
+        builtins::csv_parse(&values[0])
+      }
+      "RegexMatch" => {
+        let values = self.evaluate_arguments(identifier, arguments, 2)?;
+        builtins::regex_match(&values[0], &values[1])
+      }
+      "RegexFind" => {
+        let values = self.evaluate_arguments(identifier, arguments, 2)?;
+        builtins::regex_find(&values[0], &values[1])
+      }
+      "RegexReplace" => {
+        let values = self.evaluate_arguments(identifier, arguments, 3)?;
+        builtins::regex_replace(&values[0], &values[1], &values[2])
+      }
+      // `FormatTime(format)` formats the current time, read from the Clock.
+      "FormatTime" if arguments.len() == 1 => {
+        let values = self.evaluate_arguments(identifier, arguments, 1)?;
+        let seconds = self
+          .clock
+          .as_ref()
+          .and_then(|clock| clock.borrow_mut().unix_seconds())
+          .ok_or_else(|| {
+            RuntimeError::InvalidArgument(
+              "FormatTime needs a Clock that knows the date".to_string(),
+            )
+          })?;
+        builtins::format_unix_time(seconds, &values[0])
+      }
+      "FormatTime" => {
+        let values = self.evaluate_arguments(identifier, arguments, 2)?;
+        builtins::format_time(&values[0], &values[1])
+      }
+      _ => Err(RuntimeError::UnknownMethod(identifier.to_string())),
+    }
+  }
+
+  fn builtin_spelling(identifier: &str) -> &str {
+    METHODS
+      .iter()
+      .find(|name| name.eq_ignore_ascii_case(identifier))
+      .copied()
+      .unwrap_or(identifier)
+  }
+
+  fn method_available(&self, name: &str) -> bool {
+    if self.disabled_methods.contains(name) {
+      return false;
+    }
+    match name {
+      "Sleep" => self.timer.is_some(),
+      _ => true,
+    }
+  }
+
+  fn evaluate_arguments(
+    &mut self,
+    method: &str,
+    arguments: &[Expression],
+    expected: usize,
+  ) -> Result<Vec<Object>, RuntimeError> {
+    let values = self.evaluate_elements(arguments)?;
+    if values.len() != expected {
+      return Err(RuntimeError::WrongNumberOfArguments {
+        method: method.to_string(),
+        expected,
+        actual: values.len(),
+      });
+    }
+    Ok(values)
+  }
+
+  // Evaluates the expressions in order, expanding `...array` in place.
+  fn evaluate_elements(&mut self, expressions: &[Expression]) -> Result<Vec<Object>, RuntimeError> {
+    let mut values = vec![];
+    for e in expressions {
+      match e {
+        Expression::Spread(array) => match self.execute_expression(array)? {
+          Object::Array(elements) => values.extend(elements.iter().cloned()),
+          v => {
+            return Err(RuntimeError::TypeMismatch {
+              expected: RuntimeType::Array,
+              actual: v.type_of(),
+            })
+          }
+        },
+        _ => values.push(self.execute_expression(e)?),
+      }
+    }
+    Ok(values)
+  }
+
+  // Adds or subtracts one in place, for Inc and Dec.
+  fn step_variable(
+    &mut self,
+    name: &str,
+    operator: BinaryOperator,
+  ) -> Result<Object, RuntimeError> {
+    let n = match self.get_variable(name) {
+      Some(Object::Integer(n)) => n,
+      Some(other) => {
+        return Err(RuntimeError::TypeMismatch {
+          expected: RuntimeType::Integer,
+          actual: other.type_of(),
+        })
+      }
+      None => return Err(RuntimeError::UndefinedVariable(name.to_string())),
+    };
+    let stepped = match operator {
+      BinaryOperator::SUB => n.checked_sub(1),
+      _ => n.checked_add(1),
+    };
+    let value = Object::Integer(stepped.ok_or(RuntimeError::IntegerOverflow { operator })?);
+    self.assign_variable(name, &value)?;
+    Ok(value)
+  }
+
+  fn set_variable_keys(&mut self, name: &str) -> Result<Rc<BTreeSet<SetKey>>, RuntimeError> {
+    match self.get_variable(name) {
+      Some(Object::Set(keys)) => Ok(keys),
+      Some(v) => Err(RuntimeError::TypeMismatch {
+        expected: RuntimeType::Set,
+        actual: v.type_of(),
+      }),
+      None => Err(RuntimeError::UndefinedVariable(name.to_string())),
+    }
+  }
+
+  fn set_keys_mut(value: &mut Object) -> Result<&mut BTreeSet<SetKey>, RuntimeError> {
+    match value {
+      Object::Set(keys) => Ok(Rc::make_mut(keys)),
+      v => Err(RuntimeError::TypeMismatch {
+        expected: RuntimeType::Set,
+        actual: v.type_of(),
+      }),
+    }
+  }
+
+  // For methods called as `Method(variable, value)`.
+  fn variable_and_value(
+    &mut self,
+    method: &str,
+    arguments: &[Expression],
+  ) -> Result<(String, Object), RuntimeError> {
+    if arguments.len() != 2 {
+      return Err(RuntimeError::WrongNumberOfArguments {
+        method: method.to_string(),
+        expected: 2,
+        actual: arguments.len(),
+      });
+    }
+    let name = Self::variable_name(method, &arguments[0])?;
+    let value = self.execute_expression(&arguments[1])?;
+    Ok((name, value))
+  }
+
+  fn heap_variable(&mut self, name: &str) -> Result<Heap, RuntimeError> {
+    match self.get_variable(name) {
+      Some(Object::Heap(heap)) => Ok(heap),
+      Some(v) => Err(RuntimeError::TypeMismatch {
+        expected: RuntimeType::Heap,
+        actual: v.type_of(),
+      }),
+      None => Err(RuntimeError::UndefinedVariable(name.to_string())),
+    }
+  }
+
+  fn heap_mut(value: &mut Object) -> Result<&mut Heap, RuntimeError> {
+    match value {
+      Object::Heap(heap) => Ok(heap),
+      v => Err(RuntimeError::TypeMismatch {
+        expected: RuntimeType::Heap,
+        actual: v.type_of(),
+      }),
+    }
+  }
+
+  // An empty heap yields Undefined, or an error in strict mode.
+  fn heap_value(&self, method: &str, value: Option<i32>) -> Result<Object, RuntimeError> {
+    match value {
+      Some(n) => Ok(Object::Integer(n)),
+      None if self.strict => Err(RuntimeError::InvalidArgument(format!(
+        "{} on an empty heap",
+        method
+      ))),
+      None => Ok(Object::Undefined),
+    }
+  }
+
+  fn variable_name(method: &str, argument: &Expression) -> Result<String, RuntimeError> {
+    match argument {
+      Expression::Identifier(name) => Ok(name.to_string()),
+      _ => Err(RuntimeError::InvalidArgument(format!(
+        "{} expects a variable name",
+        method
+      ))),
+    }
+  }
+
+  fn variable_argument(method: &str, arguments: &[Expression]) -> Result<String, RuntimeError> {
+    match arguments {
+      [argument] => Self::variable_name(method, argument),
+      _ => Err(RuntimeError::WrongNumberOfArguments {
+        method: method.to_string(),
+        expected: 1,
+        actual: arguments.len(),
+      }),
+    }
+  }
+
+  fn execute_const_assignment(
+    &mut self,
+    identifier: String,
+    expression: &Expression,
+    binding: Binding,
+  ) -> Result<Object, RuntimeError> {
+    let evaluated = self.execute_expression(expression)?;
+    self.bind_variable(&identifier, &evaluated, binding)?;
+    Ok(evaluated)
+  }
+
+  fn execute_multiple_assignment(
+    &mut self,
+    identifiers: &[String],
+    expressions: &[Expression],
+    binding: Binding,
+  ) -> Result<Object, RuntimeError> {
+    // Evaluate every value before binding any name, so `a, b = b, a` swaps.
+    let mut values = vec![];
+    for e in expressions {
+      values.push(self.execute_expression(e)?);
+    }
+    if let [Object::Array(elements)] = values.as_slice() {
+      values = elements.to_vec();
+    }
+    if values.len() != identifiers.len() {
+      return Err(RuntimeError::UnpackMismatch {
+        expected: identifiers.len(),
+        actual: values.len(),
+      });
+    }
+    let mut r = Object::Undefined;
+    for (identifier, value) in identifiers.iter().zip(values) {
+      self.bind_variable(identifier, &value, binding)?;
+      r = value;
+    }
+    Ok(r)
+  }
+
+  fn execute_expression(&mut self, expression: &Expression) -> Result<Object, RuntimeError> {
+    match expression {
+      Expression::Identifier(name) => match self.get_variable(name) {
+        Some(value) => Ok(value),
+        None => match self.undefined_read {
+          UndefinedRead::Undefined => Ok(Object::Undefined),
+          UndefinedRead::Zero => Ok(Object::Integer(0)),
+          UndefinedRead::Error => Err(RuntimeError::UndefinedVariable(name.to_string())),
+        },
+      },
+      Expression::Integer(value) => Ok(Object::Integer(*value)),
+      Expression::Float(value) => Ok(Object::Float(*value)),
+      Expression::String(value) => Ok(Object::String(value.clone())),
+      Expression::Boolean(value) => Ok(Object::Boolean(*value)),
+      Expression::MethodInvocation {
+        identifier,
+        arguments,
+      } => self.execute_method(identifier, arguments),
+      Expression::Sequence(expressions) => {
+        let mut r = Object::Undefined;
+        for e in expressions {
+          r = self.execute_expression(e)?;
+        }
+        Ok(r)
+      }
+      Expression::Array(elements) => Ok(Object::Array(Rc::new(self.evaluate_elements(elements)?))),
+      Expression::Spread(_) => Err(RuntimeError::InvalidArgument(String::from(
+        "... is only allowed in array literals and argument lists",
+      ))),
+      Expression::Index { expression, index } => {
+        let target = self.execute_expression(expression)?;
+        let index = match self.execute_expression(index)? {
+          Object::Integer(i) => i,
+          // Out of range Floats saturate and are then reported as out of range.
+          Object::Float(n) if self.integral_float_indices && n.is_finite() && n.fract() == 0.0 => {
+            n as i32
+          }
+          other => return Err(RuntimeError::InvalidIndexType(other.type_of())),
+        };
+        match target {
+          Object::Array(items) => usize::try_from(index)
+            .ok()
+            .and_then(|i| items.get(i).cloned())
+            .ok_or(RuntimeError::IndexOutOfRange {
+              index,
+              length: items.len(),
+            }),
+          other => Err(RuntimeError::TypeMismatch {
+            expected: RuntimeType::Array,
+            actual: other.type_of(),
+          }),
+        }
+      }
+      Expression::Binary { .. } => {
+        // Walk the left spine iteratively so long left-associative chains
+        // such as `1 + 1 + ... + 1` do not recurse once per operator.
+        let mut spine = vec![];
+        let mut leftmost = expression;
+        while let Expression::Binary {
+          left,
+          operator,
+          right,
+        } = leftmost
+        {
+          spine.push((operator, right));
+          leftmost = left;
+        }
+        let mut value = self.execute_expression(leftmost)?;
+        for (operator, right) in spine.into_iter().rev() {
+          // `false And x` and `true Or x` are decided without evaluating x.
+          match (operator, &value) {
+            (BinaryOperator::AND, Object::Boolean(false))
+            | (BinaryOperator::OR, Object::Boolean(true)) => continue,
+            _ => {}
+          }
+          let r = self.execute_expression(right)?;
+          value = self.evaluate_binary(value, operator, r)?;
+        }
+        Ok(value)
+      }
+      Expression::Unary {
+        operator,
+        expression,
+      } => {
+        let evaluated = self.execute_expression(expression)?;
         match operator {
           UnaryOperator::NEGATIVE => match evaluated {
-            Object::Integer(n) => Ok(Object::Integer(-n)),
+            Object::Integer(n) => Self::checked_integer(n.checked_neg(), &BinaryOperator::SUB),
+            Object::Float(n) => Ok(Object::Float(-n)),
             _ => Err(RuntimeError::TypeMismatch {
               expected: RuntimeType::Integer,
               actual: RuntimeType::Integer,
@@ -218,6 +1572,7 @@ impl Executor {
           },
           UnaryOperator::POSITIVE => match evaluated {
             Object::Integer(n) => Ok(Object::Integer(n)),
+            Object::Float(n) => Ok(Object::Float(n)),
             _ => Err(RuntimeError::TypeMismatch {
               expected: RuntimeType::Integer,
               actual: RuntimeType::Integer,
@@ -234,4 +1589,1782 @@ impl Executor {
       }
     }
   }
+
+  fn evaluate_binary(
+    &self,
+    l: Object,
+    operator: &BinaryOperator,
+    r: Object,
+  ) -> Result<Object, RuntimeError> {
+    if let BinaryOperator::GT | BinaryOperator::GE | BinaryOperator::LT | BinaryOperator::LE =
+      operator
+    {
+      Self::ensure_orderable(&l)?;
+      Self::ensure_orderable(&r)?;
+    }
+    match (l, r) {
+      (Object::Integer(l), Object::Integer(r)) => match operator {
+        BinaryOperator::ADD => Self::checked_integer(l.checked_add(r), operator),
+        BinaryOperator::SUB => Self::checked_integer(l.checked_sub(r), operator),
+        BinaryOperator::MUL => Self::checked_integer(l.checked_mul(r), operator),
+        BinaryOperator::DIV | BinaryOperator::MOD if r == 0 => Err(RuntimeError::DivisionByZero),
+        BinaryOperator::DIV => Self::checked_integer(l.checked_div(r), operator),
+        BinaryOperator::MOD => Self::checked_integer(l.checked_rem(r), operator),
+        BinaryOperator::EQ => Ok(Object::Boolean(l == r)),
+        BinaryOperator::NE => Ok(Object::Boolean(l != r)),
+        BinaryOperator::GT => Ok(Object::Boolean(l > r)),
+        BinaryOperator::LT => Ok(Object::Boolean(l < r)),
+        BinaryOperator::LE => Ok(Object::Boolean(l <= r)),
+        BinaryOperator::GE => Ok(Object::Boolean(l >= r)),
+        _ => Err(RuntimeError::TypeMismatch {
+          expected: RuntimeType::Integer,
+          actual: RuntimeType::Boolean,
+        }),
+      },
+      (Object::Boolean(l), Object::Boolean(r)) => match operator {
+        BinaryOperator::AND => Ok(Object::Boolean(l && r)),
+        BinaryOperator::XOR => Ok(Object::Boolean(l ^ r)),
+        BinaryOperator::OR => Ok(Object::Boolean(l || r)),
+        BinaryOperator::EQ => Ok(Object::Boolean(l == r)),
+        BinaryOperator::NE => Ok(Object::Boolean(l != r)),
+        BinaryOperator::GT => Ok(Object::Boolean(l & !r)),
+        BinaryOperator::LT => Ok(Object::Boolean(!l & r)),
+        BinaryOperator::GE => Ok(Object::Boolean(l >= r)),
+        BinaryOperator::LE => Ok(Object::Boolean(l <= r)),
+        _ => Err(RuntimeError::TypeMismatch {
+          expected: RuntimeType::Boolean,
+          actual: RuntimeType::Integer,
+        }),
+      },
+      (Object::Float(l), Object::Float(r)) => self.evaluate_float(l, operator, r),
+      (Object::Integer(l), Object::Float(r)) => self.evaluate_float(f64::from(l), operator, r),
+      (Object::Float(l), Object::Integer(r)) => self.evaluate_float(l, operator, f64::from(r)),
+      (l @ Object::String(_), r @ Object::Integer(_))
+      | (l @ Object::Integer(_), r @ Object::String(_))
+        if *operator == BinaryOperator::ADD =>
+      {
+        self.add_string_and_integer(l, r)
+      }
+      (Object::Array(l), Object::Array(r)) => match operator {
+        BinaryOperator::EQ => Ok(Object::Boolean(l == r)),
+        BinaryOperator::NE => Ok(Object::Boolean(l != r)),
+        _ => Err(RuntimeError::TypeMismatch {
+          expected: RuntimeType::Integer,
+          actual: RuntimeType::Array,
+        }),
+      },
+      (Object::String(l), Object::String(r)) => match operator {
+        BinaryOperator::ADD => Ok(Object::String(l + &r)),
+        BinaryOperator::EQ => Ok(Object::Boolean(l == r)),
+        BinaryOperator::NE => Ok(Object::Boolean(l != r)),
+        _ => Err(RuntimeError::TypeMismatch {
+          expected: RuntimeType::Integer,
+          actual: RuntimeType::String,
+        }),
+      },
+      (l, r) => Err(RuntimeError::TypeMismatch {
+        expected: l.type_of(),
+        actual: r.type_of(),
+      }),
+    }
+  }
+
+  fn add_string_and_integer(&self, l: Object, r: Object) -> Result<Object, RuntimeError> {
+    match self.string_plus_int {
+      StringPlusInt::Error => Err(RuntimeError::TypeMismatch {
+        expected: l.type_of(),
+        actual: r.type_of(),
+      }),
+      StringPlusInt::Concat => match (l, r) {
+        (Object::String(s), Object::Integer(n)) => Ok(Object::String(format!("{}{}", s, n))),
+        (Object::Integer(n), Object::String(s)) => Ok(Object::String(format!("{}{}", n, s))),
+        (l, r) => Err(RuntimeError::TypeMismatch {
+          expected: l.type_of(),
+          actual: r.type_of(),
+        }),
+      },
+      StringPlusInt::ParseAdd => {
+        let (l, r) = (Self::parse_integer(l)?, Self::parse_integer(r)?);
+        Self::checked_integer(l.checked_add(r), &BinaryOperator::ADD)
+      }
+    }
+  }
+
+  fn parse_integer(value: Object) -> Result<i32, RuntimeError> {
+    match value {
+      Object::String(s) => s
+        .trim()
+        .parse()
+        .map_err(|_| RuntimeError::InvalidConversion(format!("'{}' is not an Integer", s))),
+      Object::Integer(n) => Ok(n),
+      v => Err(RuntimeError::TypeMismatch {
+        expected: RuntimeType::Integer,
+        actual: v.type_of(),
+      }),
+    }
+  }
+
+  // An Integer operand is promoted, so any arithmetic with a Float is a Float.
+  fn evaluate_float(
+    &self,
+    l: f64,
+    operator: &BinaryOperator,
+    r: f64,
+  ) -> Result<Object, RuntimeError> {
+    match operator {
+      BinaryOperator::ADD => self.checked_float(l + r, operator),
+      BinaryOperator::SUB => self.checked_float(l - r, operator),
+      BinaryOperator::MUL => self.checked_float(l * r, operator),
+      BinaryOperator::DIV => self.checked_float(l / r, operator),
+      BinaryOperator::MOD => self.checked_float(l % r, operator),
+      BinaryOperator::EQ => Ok(Object::Boolean(l == r)),
+      BinaryOperator::NE => Ok(Object::Boolean(l != r)),
+      BinaryOperator::GT => Ok(Object::Boolean(l > r)),
+      BinaryOperator::LT => Ok(Object::Boolean(l < r)),
+      BinaryOperator::LE => Ok(Object::Boolean(l <= r)),
+      BinaryOperator::GE => Ok(Object::Boolean(l >= r)),
+      _ => Err(RuntimeError::TypeMismatch {
+        expected: RuntimeType::Boolean,
+        actual: RuntimeType::Float,
+      }),
+    }
+  }
+
+  fn checked_float(&self, value: f64, operator: &BinaryOperator) -> Result<Object, RuntimeError> {
+    if self.float_policy == FloatPolicy::Error && !value.is_finite() {
+      return Err(RuntimeError::FloatingPointError {
+        operator: operator.clone(),
+      });
+    }
+    Ok(Object::Float(value))
+  }
+
+  fn checked_integer(
+    value: Option<i32>,
+    operator: &BinaryOperator,
+  ) -> Result<Object, RuntimeError> {
+    value
+      .map(Object::Integer)
+      .ok_or(RuntimeError::IntegerOverflow {
+        operator: operator.clone(),
+      })
+  }
+
+  fn ensure_orderable(value: &Object) -> Result<(), RuntimeError> {
+    match value.type_of() {
+      t @ RuntimeType::Undefined => Err(RuntimeError::NotOrderable(t)),
+      _ => Ok(()),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::compiled_program::CompiledProgram;
+  use crate::eval::{eval_str, run_capturing, EvalError};
+  use crate::parser::parse_source;
+
+  fn run(executor: &mut Executor, source: &str) -> Result<Object, RuntimeError> {
+    executor.execute(&parse_source(source, "<test>").unwrap())
+  }
+
+  #[test]
+  fn changing_a_copied_heap_leaves_the_original_alone() {
+    let source =
+      "Dim a = Heap()\nHeapPush(a, 3)\nDim b = a\nHeapPush(b, 1)\nHeapPop(a)\nDim x = HeapPeek(a)";
+    assert_eq!(eval_str(source), Ok(Object::Undefined));
+    let source = "Dim a = Heap()\nHeapPush(a, 3)\nDim b = a\nHeapPush(b, 1)\nDim x = HeapPeek(a)";
+    assert_eq!(eval_str(source), Ok(Object::Integer(3)));
+  }
+
+  #[test]
+  fn changing_a_copied_set_leaves_the_original_alone() {
+    let source = "Dim a = Set()\nSetAdd(a, 1)\nDim b = a\nSetRemove(b, 1)\nSetAdd(b, 2)\nDim x = SetHas(a, 1) And Not SetHas(a, 2)";
+    assert_eq!(eval_str(source), Ok(Object::Boolean(true)));
+  }
+
+  #[test]
+  fn heap_and_set_methods_check_the_variable() {
+    let mut executor = Executor::new();
+    run(&mut executor, "Const h = Heap()\nDim n = 1").unwrap();
+    assert_eq!(
+      run(&mut executor, "HeapPush(h, 1)"),
+      Err(RuntimeError::AssignToConst("h".to_string()))
+    );
+    assert_eq!(
+      run(&mut executor, "SetAdd(n, 1)"),
+      Err(RuntimeError::TypeMismatch {
+        expected: RuntimeType::Set,
+        actual: RuntimeType::Integer,
+      })
+    );
+    assert_eq!(
+      run(&mut executor, "HeapPop(missing)"),
+      Err(RuntimeError::UndefinedVariable("missing".to_string()))
+    );
+  }
+
+  #[test]
+  fn watchers_see_heap_changes() {
+    let seen = Rc::new(RefCell::new(vec![]));
+    let mut executor = Executor::new();
+    run(&mut executor, "Dim h = Heap()").unwrap();
+    let log = seen.clone();
+    executor.watch(
+      "h",
+      Box::new(move |old, new| match (old, new) {
+        (Object::Heap(old), Object::Heap(new)) => log.borrow_mut().push((old.len(), new.len())),
+        _ => panic!("expected heaps"),
+      }),
+    );
+    run(&mut executor, "HeapPush(h, 5)\nHeapPush(h, 6)\nHeapPop(h)").unwrap();
+    assert_eq!(*seen.borrow(), vec![(0, 1), (1, 2), (2, 1)]);
+  }
+
+  fn float(executor: &mut Executor, source: &str) -> Result<f64, RuntimeError> {
+    match run(executor, source)? {
+      Object::Float(n) => Ok(n),
+      v => panic!("expected a Float, got {}", v),
+    }
+  }
+
+  #[test]
+  fn floats_follow_ieee_by_default() {
+    let mut executor = Executor::new();
+    assert!(float(&mut executor, "Dim x = 0.0 / 0.0").unwrap().is_nan());
+    assert_eq!(float(&mut executor, "Dim x = 1.0 / 0.0"), Ok(f64::INFINITY));
+    assert_eq!(
+      float(&mut executor, "Dim x = -1 / 0.0"),
+      Ok(f64::NEG_INFINITY)
+    );
+  }
+
+  #[test]
+  fn the_error_policy_rejects_nan_and_infinity() {
+    let mut executor = Executor::new();
+    executor.set_float_policy(FloatPolicy::Error);
+    let error = Err(RuntimeError::FloatingPointError {
+      operator: BinaryOperator::DIV,
+    });
+    assert_eq!(float(&mut executor, "Dim x = 0.0 / 0.0"), error);
+    assert_eq!(float(&mut executor, "Dim x = 1.0 / 0.0"), error);
+    assert_eq!(float(&mut executor, "Dim x = 1.0 / 4"), Ok(0.25));
+  }
+
+  #[test]
+  fn integer_division_by_zero_is_an_error_under_both_policies() {
+    for policy in [FloatPolicy::Ieee, FloatPolicy::Error] {
+      let mut executor = Executor::new();
+      executor.set_float_policy(policy);
+      assert_eq!(
+        run(&mut executor, "Dim x = 1 / 0"),
+        Err(RuntimeError::DivisionByZero)
+      );
+    }
+  }
+
+  #[test]
+  fn indices_must_be_integers_by_default() {
+    let mut executor = Executor::new();
+    run(&mut executor, "Dim a = [10, 20, 30]").unwrap();
+    assert_eq!(
+      run(&mut executor, "Dim x = a[true]"),
+      Err(RuntimeError::InvalidIndexType(RuntimeType::Boolean))
+    );
+    assert_eq!(
+      run(&mut executor, "Dim x = a[2.0]"),
+      Err(RuntimeError::InvalidIndexType(RuntimeType::Float))
+    );
+  }
+
+  #[test]
+  fn integral_float_indices_are_truncated_when_enabled() {
+    let mut executor = Executor::new();
+    executor.set_integral_float_indices(true);
+    run(&mut executor, "Dim a = [10, 20, 30]").unwrap();
+    assert_eq!(
+      run(&mut executor, "Dim x = a[2.0]"),
+      Ok(Object::Integer(30))
+    );
+    assert_eq!(
+      run(&mut executor, "Dim x = a[1.5]"),
+      Err(RuntimeError::InvalidIndexType(RuntimeType::Float))
+    );
+    assert_eq!(
+      run(&mut executor, "Dim x = a[true]"),
+      Err(RuntimeError::InvalidIndexType(RuntimeType::Boolean))
+    );
+  }
+
+  fn overflow(source: &str, operator: BinaryOperator) {
+    let result = run_capturing(source);
+    assert_eq!(
+      result.error,
+      Some(EvalError::Runtime(RuntimeError::IntegerOverflow {
+        operator
+      }))
+    );
+    assert_eq!(result.stdout, "");
+  }
+
+  #[test]
+  fn integer_arithmetic_reports_overflow() {
+    overflow("Print(2147483647 + 1)", BinaryOperator::ADD);
+    overflow("Print(-2147483648 - 1)", BinaryOperator::SUB);
+    overflow("Print(-2147483648 * -1)", BinaryOperator::MUL);
+    overflow("Print(-2147483648 / -1)", BinaryOperator::DIV);
+    overflow("Dim n = -2147483648\nPrint(-n)", BinaryOperator::SUB);
+    assert_eq!(
+      run_capturing("Print(2147483646 + 1)").stdout,
+      "Integer(2147483647)\n"
+    );
+  }
+
+  #[derive(Clone, Default)]
+  struct Captured(Rc<RefCell<Vec<u8>>>);
+
+  impl Write for Captured {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+      self.0.borrow_mut().extend_from_slice(buf);
+      Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+      Ok(())
+    }
+  }
+
+  impl Captured {
+    fn text(&self) -> String {
+      String::from_utf8(self.0.borrow().clone()).unwrap()
+    }
+  }
+
+  fn printed(buffered: bool, source: &str) -> String {
+    let captured = Captured::default();
+    let mut executor = Executor::new();
+    executor.set_output(Box::new(captured.clone()));
+    executor.set_buffered(buffered);
+    run(&mut executor, source).unwrap();
+    captured.text()
+  }
+
+  #[test]
+  fn buffered_output_matches_unbuffered_output() {
+    let source = "For i = 1 To 3\nPrint(i)\nNext\nEPrint(0)\nFlush()\nPrint(\"done\")";
+    assert_eq!(printed(true, source), printed(false, source));
+    assert_eq!(
+      printed(true, source),
+      "Integer(1)\nInteger(2)\nInteger(3)\ndone\n"
+    );
+  }
+
+  #[test]
+  fn buffered_output_waits_for_a_flush() {
+    let captured = Captured::default();
+    let mut executor = Executor::new();
+    executor.set_output(Box::new(captured.clone()));
+    executor.set_buffered(true);
+    let program = parse_source("Print(1)", "<test>").unwrap();
+    executor.execute_program(&program).unwrap();
+    assert_eq!(captured.text(), "");
+    run(&mut executor, "Flush()").unwrap();
+    assert_eq!(captured.text(), "Integer(1)\n");
+  }
+
+  #[test]
+  fn a_string_builder_matches_concatenation() {
+    let naive = "Dim s = \"\"\nFor i = 1 To 100\ns = s + \"ab\"\nNext\nDim r = s";
+    let builder =
+      "Dim b = StringBuilder()\nFor i = 1 To 100\nAppend(b, \"ab\")\nNext\nDim r = Build(b)";
+    assert_eq!(eval_str(builder), eval_str(naive));
+    assert_eq!(eval_str(builder), Ok(Object::String("ab".repeat(100))));
+  }
+
+  #[test]
+  fn append_needs_a_string_builder_and_a_string() {
+    let mut executor = Executor::new();
+    run(&mut executor, "Dim b = StringBuilder()\nDim s = \"\"").unwrap();
+    assert_eq!(
+      run(&mut executor, "Append(s, \"x\")"),
+      Err(RuntimeError::TypeMismatch {
+        expected: RuntimeType::StringBuilder,
+        actual: RuntimeType::String,
+      })
+    );
+    assert_eq!(
+      run(&mut executor, "Append(b, 1)"),
+      Err(RuntimeError::TypeMismatch {
+        expected: RuntimeType::String,
+        actual: RuntimeType::Integer,
+      })
+    );
+  }
+
+  #[cfg(feature = "wasm")]
+  #[test]
+  fn the_wasm_build_has_no_default_sinks_timer_or_clock() {
+    let mut executor = Executor::new();
+    assert!(executor.clock.is_none());
+    assert_eq!(run(&mut executor, "Print(1)\nEPrint(2)"), Ok(Object::Unit));
+    assert_eq!(
+      run(&mut executor, "Sleep(1)"),
+      Err(RuntimeError::UnknownMethod("Sleep".to_string()))
+    );
+  }
+
+  #[test]
+  fn builtin_names_ignore_case_when_names_do() {
+    let captured = Captured::default();
+    let mut executor = Executor::new();
+    executor.set_output(Box::new(captured.clone()));
+    assert_eq!(
+      run(&mut executor, "PRINT(1)"),
+      Err(RuntimeError::UnknownMethod("PRINT".to_string()))
+    );
+    executor.set_case_insensitive(true);
+    run(&mut executor, "Dim x = 2\nPRINT(X)\nprint(len([1]))").unwrap();
+    assert_eq!(captured.text(), "Integer(2)\nInteger(1)\n");
+    assert_eq!(
+      run(&mut executor, "NOPE()"),
+      Err(RuntimeError::UnknownMethod("NOPE".to_string()))
+    );
+  }
+
+  #[test]
+  fn div_mod_returns_the_quotient_and_remainder() {
+    assert_eq!(
+      eval_str("Dim x = DivMod(17, 5) = [3, 2]"),
+      Ok(Object::Boolean(true))
+    );
+    assert_eq!(
+      eval_str("Dim x = DivMod(-17, 5) = [-3, -2]"),
+      Ok(Object::Boolean(true))
+    );
+    assert_eq!(
+      eval_str("Dim x = DivMod(1, 0)"),
+      Err(EvalError::Runtime(RuntimeError::DivisionByZero))
+    );
+  }
+
+  #[test]
+  fn an_array_result_is_unpacked_into_several_names() {
+    let mut executor = Executor::new();
+    run(&mut executor, "Dim q, r = DivMod(17, 5)").unwrap();
+    assert_eq!(executor.get_variable("q"), Some(Object::Integer(3)));
+    assert_eq!(executor.get_variable("r"), Some(Object::Integer(2)));
+    run(&mut executor, "q, r = DivMod(9, 4)").unwrap();
+    assert_eq!(executor.get_variable("r"), Some(Object::Integer(1)));
+    assert_eq!(
+      run(&mut executor, "a, b = DivMod(9, 4)"),
+      Err(RuntimeError::UndefinedVariable("a".to_string()))
+    );
+    assert_eq!(
+      run(&mut executor, "Dim x, y, z = DivMod(9, 4)"),
+      Err(RuntimeError::UnpackMismatch {
+        expected: 3,
+        actual: 2,
+      })
+    );
+  }
+
+  // 2021-03-04T05:06:07Z
+  struct FixedClock;
+
+  impl Clock for FixedClock {
+    fn now(&mut self) -> Duration {
+      Duration::from_secs(0)
+    }
+
+    fn unix_seconds(&mut self) -> Option<i64> {
+      Some(1_614_834_367)
+    }
+  }
+
+  struct ElapsedClock;
+
+  impl Clock for ElapsedClock {
+    fn now(&mut self) -> Duration {
+      Duration::from_secs(0)
+    }
+  }
+
+  #[test]
+  fn format_time_renders_a_known_timestamp() {
+    assert_eq!(
+      eval_str("Dim s = FormatTime(1614834367, \"%Y-%m-%d %H:%M:%S\")"),
+      Ok(Object::String("2021-03-04 05:06:07".to_string()))
+    );
+    assert!(matches!(
+      eval_str("Dim s = FormatTime(0, \"%Q\")"),
+      Err(EvalError::Runtime(RuntimeError::InvalidArgument(_)))
+    ));
+  }
+
+  #[test]
+  fn format_time_reads_the_current_time_from_the_clock() {
+    let mut executor = Executor::new();
+    executor.set_clock(Box::new(FixedClock));
+    assert_eq!(
+      run(&mut executor, "Dim s = FormatTime(\"%d/%m/%Y\")"),
+      Ok(Object::String("04/03/2021".to_string()))
+    );
+    executor.set_clock(Box::new(ElapsedClock));
+    assert!(matches!(
+      run(&mut executor, "Dim s = FormatTime(\"%Y\")"),
+      Err(RuntimeError::InvalidArgument(_))
+    ));
+  }
+
+  #[test]
+  fn ordering_undefined_is_not_orderable() {
+    let mut executor = Executor::new();
+    executor.set_undefined_read(UndefinedRead::Undefined);
+    for source in ["Dim x = missing < 1", "Dim x = 1 >= missing"] {
+      assert_eq!(
+        run(&mut executor, source),
+        Err(RuntimeError::NotOrderable(RuntimeType::Undefined))
+      );
+    }
+  }
+
+  fn truth(source: &str) -> bool {
+    match eval_str(&format!("Dim x = {}", source)) {
+      Ok(Object::Boolean(b)) => b,
+      other => panic!("{}: expected a Boolean, got {:?}", source, other),
+    }
+  }
+
+  #[test]
+  fn booleans_compare_with_false_below_true() {
+    let cases = [
+      ("true = true", true),
+      ("true = false", false),
+      ("true <> false", true),
+      ("false <> false", false),
+      ("true > false", true),
+      ("false > true", false),
+      ("false < true", true),
+      ("true < true", false),
+      ("true >= true", true),
+      ("false >= true", false),
+      ("false <= false", true),
+      ("true <= false", false),
+    ];
+    for (source, expected) in cases.iter() {
+      assert_eq!(truth(source), *expected, "{}", source);
+    }
+  }
+
+  #[test]
+  fn variable_names_lists_visible_names_in_order() {
+    let mut executor = Executor::new();
+    run(&mut executor, "Dim zeta = 1\nConst alpha = 2\nDim mid = 3").unwrap();
+    assert_eq!(executor.variable_names(), vec!["alpha", "mid", "zeta"]);
+    run(&mut executor, "If true Then\n  Dim inner = 4\nEnd If").unwrap();
+    assert_eq!(executor.variable_names(), vec!["alpha", "mid", "zeta"]);
+  }
+
+  #[test]
+  fn unset_removes_a_variable() {
+    let mut executor = Executor::new();
+    run(&mut executor, "Dim x = 1\nUnset(x)").unwrap();
+    assert_eq!(
+      run(&mut executor, "Dim y = x"),
+      Err(RuntimeError::UndefinedVariable("x".to_string()))
+    );
+    run(&mut executor, "Const c = 1").unwrap();
+    assert_eq!(
+      run(&mut executor, "Unset(c)"),
+      Err(RuntimeError::AssignToConst("c".to_string()))
+    );
+  }
+
+  #[test]
+  fn unsetting_an_unknown_name_is_an_error_only_in_strict_mode() {
+    let mut executor = Executor::new();
+    assert_eq!(run(&mut executor, "Unset(nothing)"), Ok(Object::Unit));
+    executor.set_strict(true);
+    assert_eq!(
+      run(&mut executor, "Unset(nothing)"),
+      Err(RuntimeError::UndefinedVariable("nothing".to_string()))
+    );
+  }
+
+  #[test]
+  fn truthy_follows_the_rules_for_each_type() {
+    let mut executor = Executor::new();
+    executor.set_undefined_read(UndefinedRead::Undefined);
+    let cases = [
+      ("0", false),
+      ("-3", true),
+      ("0.0", false),
+      ("0.5", true),
+      ("false", false),
+      ("true", true),
+      ("\"\"", false),
+      ("\"a\"", true),
+      ("[]", false),
+      ("[0]", true),
+      ("missing", false),
+      ("Heap()", false),
+      ("Set()", false),
+      ("StringBuilder()", false),
+    ];
+    for (value, expected) in cases.iter() {
+      let source = format!("Dim x = Truthy({})", value);
+      assert_eq!(
+        run(&mut executor, &source),
+        Ok(Object::Boolean(*expected)),
+        "{}",
+        value
+      );
+    }
+  }
+
+  #[test]
+  fn several_names_are_declared_in_one_statement() {
+    let mut executor = Executor::new();
+    run(&mut executor, "Const a, b = 1, \"two\"\nDim c, d = [3, 4]").unwrap();
+    assert_eq!(executor.get_variable("a"), Some(Object::Integer(1)));
+    assert_eq!(
+      executor.get_variable("b"),
+      Some(Object::String("two".to_string()))
+    );
+    assert_eq!(executor.get_variable("d"), Some(Object::Integer(4)));
+    assert_eq!(
+      run(&mut executor, "a = 5"),
+      Err(RuntimeError::AssignToConst("a".to_string()))
+    );
+    assert_eq!(
+      run(&mut executor, "Dim e, f = [1, 2, 3]"),
+      Err(RuntimeError::UnpackMismatch {
+        expected: 2,
+        actual: 3,
+      })
+    );
+  }
+
+  #[test]
+  fn a_multiple_assignment_swaps() {
+    let mut executor = Executor::new();
+    run(&mut executor, "Dim a, b = 1, 2\na, b = b, a").unwrap();
+    assert_eq!(executor.get_variable("a"), Some(Object::Integer(2)));
+    assert_eq!(executor.get_variable("b"), Some(Object::Integer(1)));
+    run(&mut executor, "Dim c = 3\na, b, c = c, a, b").unwrap();
+    assert_eq!(
+      (
+        executor.get_variable("a"),
+        executor.get_variable("b"),
+        executor.get_variable("c")
+      ),
+      (
+        Some(Object::Integer(3)),
+        Some(Object::Integer(2)),
+        Some(Object::Integer(1))
+      )
+    );
+  }
+
+  #[test]
+  fn a_do_loop_runs_until_exit_do() {
+    let source = "Dim i, sum = 0, 0\nDo\n  i = i + 1\n  If i > 4 Then\n    Exit Do\n  End If\n  sum = sum + i\nLoop\nDim x = sum * 100 + i";
+    assert_eq!(eval_str(source), Ok(Object::Integer(1005)));
+  }
+
+  #[test]
+  fn exit_do_leaves_only_the_innermost_loop() {
+    let source = "Dim outer, runs = 0, 0\nDo\n  outer = outer + 1\n  Do\n    runs = runs + 1\n    Exit Do\n  Loop\n  If outer = 3 Then\n    Exit Do\n  End If\nLoop\nDim x = runs";
+    assert_eq!(eval_str(source), Ok(Object::Integer(3)));
+  }
+
+  #[test]
+  fn a_sequence_evaluates_everything_and_yields_the_last_value() {
+    let captured = Captured::default();
+    let mut executor = Executor::new();
+    executor.set_output(Box::new(captured.clone()));
+    assert_eq!(
+      run(&mut executor, "Dim x = (Print(1), Print(2), 3)"),
+      Ok(Object::Integer(3))
+    );
+    assert_eq!(captured.text(), "Integer(1)\nInteger(2)\n");
+  }
+
+  #[test]
+  fn names_are_case_sensitive_by_default() {
+    let mut executor = Executor::new();
+    run(&mut executor, "Dim Foo = 1\nDim foo = 2").unwrap();
+    assert_eq!(executor.get_variable("Foo"), Some(Object::Integer(1)));
+    assert_eq!(executor.get_variable("foo"), Some(Object::Integer(2)));
+  }
+
+  #[test]
+  fn names_can_ignore_case() {
+    let mut executor = Executor::new();
+    executor.set_case_insensitive(true);
+    run(&mut executor, "Dim Foo = 1\nDim x = FOO + 1\nDim foo = 3").unwrap();
+    assert_eq!(executor.get_variable("x"), Some(Object::Integer(2)));
+    // Colliding names are one variable, so the last declaration wins.
+    assert_eq!(executor.get_variable("Foo"), Some(Object::Integer(3)));
+    assert_eq!(executor.variable_names(), vec!["foo", "x"]);
+  }
+
+  #[test]
+  fn strict_mode_keeps_each_variable_to_one_type() {
+    let mut executor = Executor::new();
+    executor.set_strict(true);
+    run(&mut executor, "Dim x = 1\nx = 2").unwrap();
+    assert_eq!(executor.get_variable("x"), Some(Object::Integer(2)));
+    assert_eq!(
+      run(&mut executor, "x = \"two\""),
+      Err(RuntimeError::TypeMismatch {
+        expected: RuntimeType::Integer,
+        actual: RuntimeType::String,
+      })
+    );
+    assert_eq!(executor.get_variable("x"), Some(Object::Integer(2)));
+  }
+
+  #[test]
+  fn types_can_change_outside_strict_mode() {
+    assert_eq!(
+      eval_str("Dim x = 1\nx = \"two\""),
+      Ok(Object::String("two".to_string()))
+    );
+  }
+
+  struct RecordingTimer(Rc<RefCell<Vec<Duration>>>);
+
+  impl Timer for RecordingTimer {
+    fn sleep(&mut self, duration: Duration) {
+      self.0.borrow_mut().push(duration);
+    }
+  }
+
+  #[test]
+  fn sleep_goes_through_the_timer() {
+    let slept = Rc::new(RefCell::new(vec![]));
+    let mut executor = Executor::new();
+    executor.set_timer(Box::new(RecordingTimer(slept.clone())));
+    let started = std::time::Instant::now();
+    run(&mut executor, "Sleep(60000)\nSleep(0)").unwrap();
+    assert!(started.elapsed() < Duration::from_secs(10));
+    assert_eq!(
+      *slept.borrow(),
+      vec![Duration::from_millis(60000), Duration::from_millis(0)]
+    );
+    assert!(matches!(
+      run(&mut executor, "Sleep(-1)"),
+      Err(RuntimeError::InvalidArgument(_))
+    ));
+    assert_eq!(slept.borrow().len(), 2);
+  }
+
+  #[test]
+  fn state_hash_depends_only_on_the_bindings() {
+    let mut a = Executor::new();
+    let mut b = Executor::new();
+    run(&mut a, "Dim x = 1\nDim s = \"hi\"\nDim xs = [1, 2]").unwrap();
+    run(&mut b, "Dim xs = [1, 2]\nDim s = \"hi\"\nDim x = 1").unwrap();
+    assert_eq!(a.state_hash(), b.state_hash());
+    run(&mut b, "x = 2").unwrap();
+    assert_ne!(a.state_hash(), b.state_hash());
+    run(&mut b, "x = 1").unwrap();
+    assert_eq!(a.state_hash(), b.state_hash());
+  }
+
+  #[test]
+  fn equal_heaps_hash_the_same_whatever_the_push_order() {
+    let mut a = Executor::new();
+    let mut b = Executor::new();
+    run(
+      &mut a,
+      "Dim h = Heap()\nHeapPush(h, 1)\nHeapPush(h, 2)\nHeapPush(h, 3)",
+    )
+    .unwrap();
+    run(
+      &mut b,
+      "Dim h = Heap()\nHeapPush(h, 3)\nHeapPush(h, 1)\nHeapPush(h, 2)",
+    )
+    .unwrap();
+    assert_eq!(a.get_variable("h"), b.get_variable("h"));
+    assert_eq!(
+      format!("{:?}", a.get_variable("h")),
+      format!("{:?}", b.get_variable("h"))
+    );
+    assert_eq!(a.state_hash(), b.state_hash());
+    run(&mut b, "HeapPush(h, 3)").unwrap();
+    assert_ne!(a.state_hash(), b.state_hash());
+  }
+
+  #[test]
+  fn watchers_get_old_and_new_values_until_unwatched() {
+    let seen = Rc::new(RefCell::new(vec![]));
+    let mut executor = Executor::new();
+    let log = seen.clone();
+    let handle = executor.watch(
+      "x",
+      Box::new(move |old, new| log.borrow_mut().push(format!("{} -> {}", old, new))),
+    );
+    run(&mut executor, "Dim x = 1\nDim y = 5\nx = 2").unwrap();
+    executor.unwatch(handle);
+    run(&mut executor, "x = 3").unwrap();
+    assert_eq!(
+      *seen.borrow(),
+      vec![
+        format!("{} -> {}", Object::Undefined, Object::Integer(1)),
+        format!("{} -> {}", Object::Integer(1), Object::Integer(2)),
+      ]
+    );
+  }
+
+  #[test]
+  fn watchers_see_an_unset_variable_become_undefined() {
+    let seen = Rc::new(RefCell::new(vec![]));
+    let mut executor = Executor::new();
+    let log = seen.clone();
+    executor.watch(
+      "x",
+      Box::new(move |old, new| log.borrow_mut().push((old.clone(), new.clone()))),
+    );
+    run(&mut executor, "Dim x = 1\nUnset(x)\nUnset(x)").unwrap();
+    assert_eq!(
+      *seen.borrow(),
+      vec![
+        (Object::Undefined, Object::Integer(1)),
+        (Object::Integer(1), Object::Undefined),
+      ]
+    );
+  }
+
+  #[test]
+  fn estimated_memory_grows_with_stored_values() {
+    let mut executor = Executor::new();
+    run(&mut executor, "Dim n = 1").unwrap();
+    let before = executor.estimated_memory();
+    run(
+      &mut executor,
+      "Dim a = [\"some text\", [1, 2, 3, 4, 5, 6, 7, 8], 9]",
+    )
+    .unwrap();
+    let after = executor.estimated_memory();
+    assert!(after >= before + 11 * std::mem::size_of::<Object>());
+    run(&mut executor, "Unset(a)").unwrap();
+    assert_eq!(executor.estimated_memory(), before);
+  }
+
+  #[test]
+  fn a_growing_array_trips_the_memory_limit() {
+    let mut executor = Executor::new();
+    executor.set_memory_limit(Some(4096));
+    let source = "Dim a = []\nDo\n  a = [...a, a]\nLoop";
+    match run(&mut executor, source) {
+      Err(RuntimeError::MemoryLimitExceeded { limit, used }) => {
+        assert_eq!(limit, 4096);
+        assert!(used > limit);
+      }
+      r => panic!("expected MemoryLimitExceeded, got {:?}", r),
+    }
+    let mut executor = Executor::new();
+    executor.set_memory_limit(Some(4096));
+    assert!(run(&mut executor, "Dim a = [1, 2, 3]").is_ok());
+  }
+
+  #[derive(Clone, Default)]
+  struct FlushTracker(Rc<RefCell<String>>);
+
+  impl Write for FlushTracker {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+      self.0.borrow_mut().push('w');
+      Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+      self.0.borrow_mut().push('f');
+      Ok(())
+    }
+  }
+
+  fn output_events(flush_each_print: bool) -> String {
+    let tracker = FlushTracker::default();
+    let mut executor = Executor::new();
+    executor.set_output(Box::new(tracker.clone()));
+    executor.set_flush_each_print(flush_each_print);
+    run(&mut executor, "Print(1)\nPrint(2)").unwrap();
+    let events = tracker.0.borrow().clone();
+    events
+  }
+
+  #[test]
+  fn print_can_flush_after_every_call() {
+    assert_eq!(output_events(false), "wwf");
+    assert_eq!(output_events(true), "wfwff");
+  }
+
+  #[test]
+  fn execute_with_env_seeds_variables() {
+    let captured = Captured::default();
+    let mut executor = Executor::new();
+    executor.set_output(Box::new(captured.clone()));
+    run(&mut executor, "Dim x = 1").unwrap();
+    let program = parse_source("Print(x + 1)", "<test>").unwrap();
+    let env = vec![("x".to_string(), Object::Integer(10))]
+      .into_iter()
+      .collect();
+    executor.execute_with_env(&program, env).unwrap();
+    assert_eq!(captured.text(), "Integer(11)\n");
+    assert_eq!(executor.get_variable("x"), Some(Object::Integer(10)));
+  }
+
+  #[test]
+  fn execute_with_env_checks_seeds_like_assignments() {
+    let program = parse_source("Dim y = x", "<test>").unwrap();
+    let env = |value: Object| vec![("x".to_string(), value)].into_iter().collect();
+    let mut executor = Executor::new();
+    run(&mut executor, "Const x = 1").unwrap();
+    assert_eq!(
+      executor.execute_with_env(&program, env(Object::Integer(2))),
+      Err(RuntimeError::AssignToConst("x".to_string()))
+    );
+    assert_eq!(executor.get_variable("x"), Some(Object::Integer(1)));
+
+    let mut executor = Executor::new();
+    executor.set_strict(true);
+    run(&mut executor, "Dim x = 1").unwrap();
+    assert_eq!(
+      executor.execute_with_env(&program, env(Object::String("a".to_string()))),
+      Err(RuntimeError::TypeMismatch {
+        expected: RuntimeType::Integer,
+        actual: RuntimeType::String,
+      })
+    );
+
+    let mut executor = Executor::new();
+    executor.set_memory_limit(Some(100));
+    let big = Object::String("x".repeat(1000));
+    assert!(matches!(
+      executor.execute_with_env(&program, env(big)),
+      Err(RuntimeError::MemoryLimitExceeded { .. })
+    ));
+  }
+
+  #[test]
+  fn eprint_writes_to_the_error_output() {
+    let (out, err) = (Captured::default(), Captured::default());
+    let mut executor = Executor::new();
+    executor.set_output(Box::new(out.clone()));
+    executor.set_error_output(Box::new(err.clone()));
+    run(&mut executor, "Print(1)\nEPrint(\"warning\", 2)").unwrap();
+    assert_eq!(out.text(), "Integer(1)\n");
+    assert_eq!(err.text(), "warning\nInteger(2)\n");
+  }
+
+  #[test]
+  fn debug_prints_the_representation_of_each_type() {
+    let source = "Debug(RegexFind(\"z\", \"a\"))\nDebug(Print())\nDebug(1)\nDebug(1.5)\nDebug(true)\nDebug(\"a\")\nDebug(StringBuilder())\nDebug([1, [2]])\nDim h = Heap()\nHeapPush(h, 2)\nDebug(h)\nDim s = Set()\nSetAdd(s, 1)\nSetAdd(s, \"x\")\nDebug(s)";
+    assert_eq!(
+      printed(false, source),
+      "Undefined\nUnit\nInteger(1)\nFloat(1.5)\nBoolean(true)\nString(\"a\")\nStringBuilder(\"\")\nArray([Integer(1), Array([Integer(2)])])\nHeap(Heap([2]))\nSet({Integer(1), String(\"x\")})\n"
+    );
+  }
+
+  #[test]
+  fn inc_and_dec_step_a_variable() {
+    let mut executor = Executor::new();
+    run(&mut executor, "Dim n = 0").unwrap();
+    assert_eq!(
+      run(&mut executor, "Inc(n)\nInc(n)\nInc(n)"),
+      Ok(Object::Integer(3))
+    );
+    assert_eq!(run(&mut executor, "Dim m = Dec(n)"), Ok(Object::Integer(2)));
+    assert_eq!(executor.get_variable("n"), Some(Object::Integer(2)));
+    assert_eq!(
+      run(&mut executor, "Inc(missing)"),
+      Err(RuntimeError::UndefinedVariable("missing".to_string()))
+    );
+    run(&mut executor, "Dim s = \"a\"").unwrap();
+    assert!(matches!(
+      run(&mut executor, "Dec(s)"),
+      Err(RuntimeError::TypeMismatch { .. })
+    ));
+  }
+
+  #[test]
+  fn inc_and_dec_check_for_overflow() {
+    let mut executor = Executor::new();
+    run(
+      &mut executor,
+      "Dim big = 2147483647\nDim small = -2147483648",
+    )
+    .unwrap();
+    assert_eq!(
+      run(&mut executor, "Inc(big)"),
+      Err(RuntimeError::IntegerOverflow {
+        operator: BinaryOperator::ADD
+      })
+    );
+    assert_eq!(
+      run(&mut executor, "Dec(small)"),
+      Err(RuntimeError::IntegerOverflow {
+        operator: BinaryOperator::SUB
+      })
+    );
+    assert_eq!(
+      executor.get_variable("big"),
+      Some(Object::Integer(i32::MAX))
+    );
+  }
+
+  #[test]
+  fn one_long_loop_trips_the_per_loop_cap() {
+    let mut executor = Executor::new();
+    executor.set_max_loop_iterations(Some(100));
+    let short_loops = "Dim total = 0\nFor i = 1 To 50\n  For j = 1 To 100\n    total = total + 1\n  Next\nNext\nDim x = total";
+    assert_eq!(run(&mut executor, short_loops), Ok(Object::Integer(5000)));
+    assert_eq!(
+      run(&mut executor, "For k = 1 To 101\nNext"),
+      Err(RuntimeError::LoopLimitExceeded(100))
+    );
+    assert_eq!(
+      run(&mut executor, "Do\nLoop"),
+      Err(RuntimeError::LoopLimitExceeded(100))
+    );
+  }
+
+  // Returns the given readings, in milliseconds, one per call.
+  struct ScriptedClock(Vec<u64>);
+
+  impl Clock for ScriptedClock {
+    fn now(&mut self) -> Duration {
+      Duration::from_millis(self.0.remove(0))
+    }
+  }
+
+  #[test]
+  fn profiling_records_each_statement_with_the_clock() {
+    let mut executor = Executor::new();
+    executor.set_clock(Box::new(ScriptedClock(vec![0, 5, 5, 20])));
+    let program = parse_source("Dim x = 1\nDim y = x + 1", "<test>").unwrap();
+    executor.execute(&program).unwrap();
+    assert!(executor.statement_timings().is_empty());
+    executor.set_profiling(true);
+    executor.execute(&program).unwrap();
+    assert_eq!(
+      executor.statement_timings(),
+      &[
+        (0, Duration::from_millis(5)),
+        (1, Duration::from_millis(15))
+      ][..]
+    );
+  }
+
+  #[test]
+  fn coverage_leaves_out_the_branch_not_taken() {
+    let mut executor = Executor::new();
+    executor.set_coverage(true);
+    let source =
+      "Dim x = 1\nIf x > 0 Then\n  Dim a = 1\n  Dim b = 2\nElse\n  Dim c = 3\nEnd If\nDim d = 4";
+    run(&mut executor, source).unwrap();
+    let covered: Vec<usize> = executor.covered_statements().iter().cloned().collect();
+    assert_eq!(covered, vec![0, 1, 2, 3, 5]);
+    run(&mut executor, "Dim y = 1").unwrap();
+    let covered: Vec<usize> = executor.covered_statements().iter().cloned().collect();
+    assert_eq!(covered, vec![0]);
+  }
+
+  #[test]
+  fn assert_eq_passes_on_equal_values() {
+    assert!(
+      eval_str("AssertEq(1 + 1, 2)\nAssertEq(\"a\", \"a\")\nAssertEq([1, [2]], [1, [2]])").is_ok()
+    );
+  }
+
+  #[test]
+  fn assert_eq_fails_on_different_values_of_any_type() {
+    assert_eq!(
+      eval_str("AssertEq(2 * 3, 5)"),
+      Err(EvalError::Runtime(RuntimeError::AssertionFailed {
+        actual: Object::Integer(6),
+        expected: Object::Integer(5),
+      }))
+    );
+    assert_eq!(
+      eval_str("AssertEq(1, \"1\")"),
+      Err(EvalError::Runtime(RuntimeError::AssertionFailed {
+        actual: Object::Integer(1),
+        expected: Object::String("1".to_string()),
+      }))
+    );
+    assert_eq!(
+      RuntimeError::AssertionFailed {
+        actual: Object::Integer(6),
+        expected: Object::Integer(5),
+      }
+      .to_string(),
+      "AssertionFailed, expected Integer(5), but actual is Integer(6)."
+    );
+  }
+
+  #[test]
+  fn version_is_the_crate_version() {
+    let version = match eval_str("Dim v = Version()") {
+      Ok(Object::String(v)) => v,
+      r => panic!("expected a String, got {:?}", r),
+    };
+    assert_eq!(version, env!("CARGO_PKG_VERSION"));
+    let parts: Vec<&str> = version.split('.').collect();
+    assert_eq!(parts.len(), 3);
+    assert!(parts.iter().all(|p| p.parse::<u32>().is_ok()));
+  }
+
+  #[test]
+  fn halt_stops_the_program_with_its_code() {
+    let result = run_capturing("Print(1)\nHalt(3)\nPrint(2)");
+    assert_eq!(result.stdout, "Integer(1)\n");
+    assert_eq!(
+      result.error,
+      Some(EvalError::Runtime(RuntimeError::Halt(3)))
+    );
+    let source = "For i = 1 To 10\n  If i = 2 Then\n    Halt(i)\n  End If\nNext";
+    assert_eq!(
+      eval_str(source),
+      Err(EvalError::Runtime(RuntimeError::Halt(2)))
+    );
+  }
+
+  #[test]
+  fn each_comparison_yields_a_boolean() {
+    for (source, expected) in [
+      ("3 == 3", true),
+      ("3 != 3", false),
+      ("3 < 5", true),
+      ("3 > 5", false),
+      ("5 <= 5", true),
+      ("4 >= 5", false),
+      ("2 * 3 == 6", true),
+    ] {
+      assert_eq!(
+        eval_str(&format!("Dim x = {}", source)),
+        Ok(Object::Boolean(expected)),
+        "{}",
+        source
+      );
+    }
+    assert_eq!(run_capturing("Print(3 < 5)").stdout, "Boolean(true)\n");
+  }
+
+  #[test]
+  fn a_heap_pops_in_sorted_order() {
+    let source = "Dim h = Heap()\nHeapPush(h, 5)\nHeapPush(h, -2)\nHeapPush(h, 9)\nHeapPush(h, 1)\nHeapPush(h, 5)\nDim top = HeapPeek(h)\nDim out = [HeapPop(h), HeapPop(h), HeapPop(h), HeapPop(h), HeapPop(h), top]";
+    let expected = [-2, 1, 5, 5, 9, -2]
+      .iter()
+      .map(|n| Object::Integer(*n))
+      .collect();
+    assert_eq!(eval_str(source), Ok(Object::Array(Rc::new(expected))));
+  }
+
+  #[test]
+  fn an_empty_heap_is_undefined_or_an_error_in_strict_mode() {
+    let mut executor = Executor::new();
+    assert_eq!(
+      run(&mut executor, "Dim h = Heap()\nDim x = HeapPop(h)"),
+      Ok(Object::Undefined)
+    );
+    assert_eq!(
+      run(&mut executor, "Dim y = HeapPeek(h)"),
+      Ok(Object::Undefined)
+    );
+    executor.set_strict(true);
+    assert!(matches!(
+      run(&mut executor, "Dim z = HeapPop(h)"),
+      Err(RuntimeError::InvalidArgument(_))
+    ));
+  }
+
+  #[test]
+  fn le_and_ge_include_equal_values() {
+    for (source, expected) in [
+      ("5 <= 5", true),
+      ("5 >= 5", true),
+      ("4 <= 5", true),
+      ("6 >= 5", true),
+      ("6 <= 5", false),
+      ("4 >= 5", false),
+      ("-1 <= -2", false),
+      ("2.5 <= 2.5", true),
+      ("2.5 >= 3", false),
+    ] {
+      assert_eq!(truth(source), expected, "{}", source);
+    }
+  }
+
+  #[test]
+  fn sets_hold_each_value_once() {
+    let source = "Dim s = Set()\nSetAdd(s, 1)\nSetAdd(s, 1)\nSetAdd(s, \"a\")\nSetAdd(s, true)\nSetAdd(s, \"a\")\nDim n = Len(s)";
+    assert_eq!(eval_str(source), Ok(Object::Integer(3)));
+  }
+
+  #[test]
+  fn set_membership_and_removal() {
+    let mut executor = Executor::new();
+    run(&mut executor, "Dim s = Set()\nSetAdd(s, 1)\nSetAdd(s, 2)").unwrap();
+    assert_eq!(
+      run(&mut executor, "Dim x = SetHas(s, 2)"),
+      Ok(Object::Boolean(true))
+    );
+    assert_eq!(
+      run(&mut executor, "Dim x = SetHas(s, \"2\")"),
+      Ok(Object::Boolean(false))
+    );
+    run(&mut executor, "SetRemove(s, 2)\nSetRemove(s, 7)").unwrap();
+    assert_eq!(
+      run(&mut executor, "Dim x = SetHas(s, 2)"),
+      Ok(Object::Boolean(false))
+    );
+    assert_eq!(run(&mut executor, "Dim x = Len(s)"), Ok(Object::Integer(1)));
+    assert_eq!(
+      run(&mut executor, "SetAdd(s, [1])"),
+      Err(RuntimeError::NotHashable(RuntimeType::Array))
+    );
+    assert_eq!(
+      run(&mut executor, "SetAdd(s, 1.5)"),
+      Err(RuntimeError::NotHashable(RuntimeType::Float))
+    );
+  }
+
+  #[test]
+  fn boolean_operators_follow_their_truth_tables() {
+    for (l, r) in [(false, false), (false, true), (true, false), (true, true)] {
+      assert_eq!(truth(&format!("{} And {}", l, r)), l && r);
+      assert_eq!(truth(&format!("{} Or {}", l, r)), l || r);
+      assert_eq!(truth(&format!("{} Xor {}", l, r)), l ^ r);
+    }
+  }
+
+  #[test]
+  fn division_and_modulo_by_zero_are_errors() {
+    let mut executor = Executor::new();
+    for source in ["Print(5 / 0)", "Print(5 % 0)", "Dim z = 0\nDim x = 5 Mod z"] {
+      assert_eq!(
+        run(&mut executor, source),
+        Err(RuntimeError::DivisionByZero),
+        "{}",
+        source
+      );
+    }
+    assert_eq!(run(&mut executor, "Dim x = 5 % 3"), Ok(Object::Integer(2)));
+  }
+
+  #[test]
+  fn unit_and_undefined_are_different_values() {
+    let mut executor = Executor::new();
+    assert_eq!(run(&mut executor, "Dim p = Print()"), Ok(Object::Unit));
+    run(&mut executor, "Dim missing = RegexFind(\"z\", \"a\")").unwrap();
+    assert_eq!(
+      run(&mut executor, "Dim t = [TypeOf(p), TypeOf(missing)]"),
+      Ok(Object::Array(Rc::new(vec![
+        Object::String("Unit".to_string()),
+        Object::String("Undefined".to_string()),
+      ])))
+    );
+    assert_ne!(Object::Unit, Object::Undefined);
+    assert_eq!(
+      run(&mut executor, "AssertEq(p, missing)"),
+      Err(RuntimeError::AssertionFailed {
+        actual: Object::Unit,
+        expected: Object::Undefined,
+      })
+    );
+    assert_eq!(run(&mut executor, "AssertEq(p, Print())"), Ok(Object::Unit));
+    assert_eq!(eval_str(""), Ok(Object::Unit));
+  }
+
+  #[test]
+  fn boolean_literals_evaluate_to_booleans() {
+    assert!(!truth("true And false"));
+    assert_eq!(
+      run_capturing("Dim x = true\nPrint(false And x)").stdout,
+      "Boolean(false)\n"
+    );
+  }
+
+  #[test]
+  fn strings_concatenate_and_print_as_text() {
+    assert_eq!(
+      run_capturing("Print(\"hello \" + \"world\")").stdout,
+      "hello world\n"
+    );
+    assert!(truth("\"ab\" = \"a\" + \"b\""));
+    assert!(truth("\"a\" <> \"b\""));
+    assert_eq!(
+      eval_str("Dim x = \"a\" + 1"),
+      Err(EvalError::Runtime(RuntimeError::TypeMismatch {
+        expected: RuntimeType::String,
+        actual: RuntimeType::Integer,
+      }))
+    );
+  }
+
+  #[test]
+  fn reading_an_unassigned_variable_follows_the_setting() {
+    let mut executor = Executor::new();
+    assert_eq!(
+      run(&mut executor, "Dim x = nothing"),
+      Err(RuntimeError::UndefinedVariable("nothing".to_string()))
+    );
+    executor.set_undefined_read(UndefinedRead::Undefined);
+    assert_eq!(run(&mut executor, "Dim x = nothing"), Ok(Object::Undefined));
+    executor.set_undefined_read(UndefinedRead::Zero);
+    assert_eq!(
+      run(&mut executor, "Dim x = nothing + 1"),
+      Ok(Object::Integer(1))
+    );
+    executor.set_undefined_read(UndefinedRead::Error);
+    assert_eq!(
+      run(&mut executor, "Dim x = nothing"),
+      Err(RuntimeError::UndefinedVariable("nothing".to_string()))
+    );
+  }
+
+  #[test]
+  fn guard_runs_its_else_block_only_when_the_condition_fails() {
+    let source = "Dim i, seen = 0, 0\nDo\n  i = i + 1\n  Guard i < 4 Else\n    Exit Do\n  End Guard\n  seen = seen + 1\nLoop\nDim x = seen * 10 + i";
+    assert_eq!(eval_str(source), Ok(Object::Integer(34)));
+    assert_eq!(
+      eval_str("Guard 1 > 2 Else\n  Halt(7)\nEnd Guard\nDim x = 1"),
+      Err(EvalError::Runtime(RuntimeError::Halt(7)))
+    );
+    assert_eq!(
+      eval_str("Guard 1 < 2 Else\n  Halt(7)\nEnd Guard\nDim x = 1"),
+      Ok(Object::Integer(1))
+    );
+  }
+
+  #[test]
+  fn float_arithmetic_and_promotion() {
+    assert_eq!(
+      run_capturing("Print(1.5 + 2.5)\nPrint(7.0 / 2.0)\nPrint(1 + 2.5)").stdout,
+      "Float(4.0)\nFloat(3.5)\nFloat(3.5)\n"
+    );
+    assert_eq!(eval_str("Dim x = 7 / 2.0"), Ok(Object::Float(3.5)));
+    assert_eq!(eval_str("Dim x = 7.5 Mod 2"), Ok(Object::Float(1.5)));
+    assert_eq!(eval_str("Dim x = 7 / 2"), Ok(Object::Integer(3)));
+    assert!(truth("2 < 2.5"));
+    assert!(truth("2.0 = 2"));
+  }
+
+  #[test]
+  fn if_runs_the_first_branch_whose_condition_holds() {
+    let program = |n: i32| {
+      format!(
+        "Dim n = {}\nDim r = 0\nIf n < 0 Then\n  r = -1\nElse If n = 0 Then\n  r = 0\nElse\n  r = 1\nEnd If\nDim x = r",
+        n
+      )
+    };
+    assert_eq!(eval_str(&program(-5)), Ok(Object::Integer(-1)));
+    assert_eq!(eval_str(&program(0)), Ok(Object::Integer(0)));
+    assert_eq!(eval_str(&program(5)), Ok(Object::Integer(1)));
+    assert_eq!(
+      eval_str("Dim r = 1\nIf false Then\n  r = 2\nEnd If\nDim x = r"),
+      Ok(Object::Integer(1))
+    );
+  }
+
+  #[test]
+  fn an_if_condition_must_be_a_boolean() {
+    assert_eq!(
+      eval_str("If 1 Then\n  Dim x = 1\nEnd If"),
+      Err(EvalError::Runtime(RuntimeError::TypeMismatch {
+        expected: RuntimeType::Boolean,
+        actual: RuntimeType::Integer,
+      }))
+    );
+  }
+
+  #[test]
+  fn the_if_builtin_evaluates_only_the_chosen_branch() {
+    assert_eq!(
+      eval_str("Dim x = If(1 < 2, \"yes\", \"no\")"),
+      Ok(Object::String("yes".to_string()))
+    );
+    assert_eq!(
+      eval_str("Dim x = If(1 > 2, \"yes\", \"no\")"),
+      Ok(Object::String("no".to_string()))
+    );
+    assert_eq!(
+      eval_str("Dim d = 0\nDim x = If(d = 0, 0, 10 / d)"),
+      Ok(Object::Integer(0))
+    );
+    assert_eq!(
+      eval_str("Dim d = 5\nDim x = If(d = 0, 1 / 0, 10 / d)"),
+      Ok(Object::Integer(2))
+    );
+    assert!(matches!(
+      eval_str("Dim x = If(1, 2, 3)"),
+      Err(EvalError::Runtime(RuntimeError::TypeMismatch { .. }))
+    ));
+  }
+
+  #[test]
+  fn a_while_loop_runs_until_its_condition_fails() {
+    let source =
+      "Dim i, sum = 1, 0\nWhile i <= 10\n  sum = sum + i\n  i = i + 1\nEnd While\nDim x = sum";
+    assert_eq!(eval_str(source), Ok(Object::Integer(55)));
+    assert_eq!(
+      eval_str("Dim n = 0\nWhile false\n  n = 1\nEnd While\nDim x = n"),
+      Ok(Object::Integer(0))
+    );
+    assert_eq!(
+      eval_str("While 1\nEnd While"),
+      Err(EvalError::Runtime(RuntimeError::TypeMismatch {
+        expected: RuntimeType::Boolean,
+        actual: RuntimeType::Integer,
+      }))
+    );
+  }
+
+  #[test]
+  fn while_iterations_count_toward_the_loop_cap() {
+    let mut executor = Executor::new();
+    executor.set_max_loop_iterations(Some(10));
+    assert_eq!(
+      run(&mut executor, "While true\nEnd While"),
+      Err(RuntimeError::LoopLimitExceeded(10))
+    );
+  }
+
+  #[test]
+  fn spread_splices_an_array_into_a_literal() {
+    let ints =
+      |ns: &[i32]| Object::Array(Rc::new(ns.iter().map(|n| Object::Integer(*n)).collect()));
+    assert_eq!(
+      eval_str("Dim rest = [2, 3]\nDim x = [1, ...rest, 4]"),
+      Ok(ints(&[1, 2, 3, 4]))
+    );
+    assert_eq!(eval_str("Dim x = [...[], ...[5]]"), Ok(ints(&[5])));
+  }
+
+  #[test]
+  fn spread_forwards_arguments_to_a_method() {
+    assert_eq!(
+      eval_str("Dim args = [17, 5]\nDim x = DivMod(...args)"),
+      eval_str("Dim x = DivMod(17, 5)")
+    );
+    assert!(eval_str("Dim pair = [3, 3]\nAssertEq(...pair)").is_ok());
+  }
+
+  #[test]
+  fn only_arrays_can_be_spread() {
+    assert_eq!(
+      eval_str("Dim x = [...1]"),
+      Err(EvalError::Runtime(RuntimeError::TypeMismatch {
+        expected: RuntimeType::Array,
+        actual: RuntimeType::Integer,
+      }))
+    );
+    assert!(matches!(
+      eval_str("Dim a = [1]\nDim x = ...a"),
+      Err(EvalError::Parse(_))
+    ));
+    let stray = Program {
+      statements: vec![Statement::Declaration {
+        identifier: "x".to_string(),
+        expression: Expression::Spread(Box::new(Expression::Array(vec![]))),
+        constant: false,
+      }],
+    };
+    assert!(matches!(
+      Executor::new().execute(&stray),
+      Err(RuntimeError::InvalidArgument(_))
+    ));
+  }
+
+  #[test]
+  fn a_block_variable_is_gone_after_the_block() {
+    assert_eq!(
+      eval_str("If true Then\n  Dim inner = 1\nEnd If\nDim x = inner"),
+      Err(EvalError::Runtime(RuntimeError::UndefinedVariable(
+        "inner".to_string()
+      )))
+    );
+  }
+
+  #[test]
+  fn blocks_can_shadow_and_assign_outer_variables() {
+    let source =
+      "Dim x = 1\nDim y = 1\nIf true Then\n  Dim x = 10\n  y = x + 1\nEnd If\nDim r = [x, y]";
+    assert_eq!(
+      eval_str(source),
+      Ok(Object::Array(Rc::new(vec![
+        Object::Integer(1),
+        Object::Integer(11),
+      ])))
+    );
+    let source = "Dim total = 0\nFor i = 1 To 3\n  Dim step = i * 2\n  total = total + step\nNext\nDim x = total";
+    assert_eq!(eval_str(source), Ok(Object::Integer(12)));
+  }
+
+  #[test]
+  fn adding_a_string_and_an_integer_follows_the_policy() {
+    let mut executor = Executor::new();
+    assert!(matches!(
+      run(&mut executor, "Dim x = \"3\" + 4"),
+      Err(RuntimeError::TypeMismatch { .. })
+    ));
+    executor.set_string_plus_int(StringPlusInt::Concat);
+    assert_eq!(
+      run(&mut executor, "Dim x = [\"3\" + 4, 4 + \"3\"]"),
+      Ok(Object::Array(Rc::new(vec![
+        Object::String("34".to_string()),
+        Object::String("43".to_string()),
+      ])))
+    );
+    executor.set_string_plus_int(StringPlusInt::ParseAdd);
+    assert_eq!(
+      run(&mut executor, "Dim x = \"3\" + 4"),
+      Ok(Object::Integer(7))
+    );
+    assert_eq!(
+      run(&mut executor, "Dim x = 4 + \"3\""),
+      Ok(Object::Integer(7))
+    );
+    assert!(matches!(
+      run(&mut executor, "Dim x = \"three\" + 4"),
+      Err(RuntimeError::InvalidConversion(_))
+    ));
+    executor.set_string_plus_int(StringPlusInt::Error);
+    assert!(matches!(
+      run(&mut executor, "Dim x = 4 + \"3\""),
+      Err(RuntimeError::TypeMismatch { .. })
+    ));
+  }
+
+  #[test]
+  fn a_const_cannot_be_changed() {
+    let mut executor = Executor::new();
+    run(&mut executor, "Const limit = 3\nDim n = 1").unwrap();
+    let error = Err(RuntimeError::AssignToConst("limit".to_string()));
+    for source in [
+      "limit = 4",
+      "Dim limit = 4",
+      "Const limit = 4",
+      "Inc(limit)",
+      "Unset(limit)",
+    ] {
+      assert_eq!(run(&mut executor, source), error, "{}", source);
+    }
+    assert_eq!(executor.get_variable("limit"), Some(Object::Integer(3)));
+  }
+
+  #[test]
+  fn mutable_variables_can_be_assigned() {
+    let mut executor = Executor::new();
+    run(&mut executor, "Const limit = 3\nDim n = 1\nn = 2").unwrap();
+    assert_eq!(executor.get_variable("n"), Some(Object::Integer(2)));
+    assert_eq!(
+      run(
+        &mut executor,
+        "If true Then\n  Dim limit = 10\n  n = limit\nEnd If\nDim x = n"
+      ),
+      Ok(Object::Integer(10))
+    );
+  }
+
+  #[test]
+  fn assignment_needs_a_declared_variable() {
+    assert_eq!(
+      eval_str("total = 1"),
+      Err(EvalError::Runtime(RuntimeError::UndefinedVariable(
+        "total".to_string()
+      )))
+    );
+    assert_eq!(eval_str("Dim total = 0\ntotal = 1"), Ok(Object::Integer(1)));
+    assert_eq!(
+      eval_str("For i = 1 To 3\nNext\nDim x = 1"),
+      Ok(Object::Integer(1))
+    );
+  }
+
+  #[test]
+  fn redeclaring_is_an_error_only_in_strict_mode() {
+    let mut executor = Executor::new();
+    assert!(run(&mut executor, "Dim x = 1\nDim x = 2").is_ok());
+    let mut executor = Executor::new();
+    executor.set_strict(true);
+    assert_eq!(
+      run(&mut executor, "Dim x = 1\nDim x = 2"),
+      Err(RuntimeError::AlreadyDeclared("x".to_string()))
+    );
+    assert!(run(&mut executor, "If true Then\n  Dim x = 3\nEnd If").is_ok());
+  }
+
+  #[test]
+  fn a_misspelled_name_surfaces_at_once() {
+    let result = run_capturing("Dim total = 3\nPrint(totl)\nPrint(total)");
+    assert_eq!(
+      result.error,
+      Some(EvalError::Runtime(RuntimeError::UndefinedVariable(
+        "totl".to_string()
+      )))
+    );
+    assert_eq!(result.stdout, "");
+  }
+
+  #[test]
+  fn float_precision_applies_to_printed_floats() {
+    let captured = Captured::default();
+    let mut executor = Executor::new();
+    executor.set_output(Box::new(captured.clone()));
+    run(&mut executor, "Print(1.0 / 3.0)").unwrap();
+    executor.set_float_precision(Some(6));
+    run(&mut executor, "Print(1.0 / 3.0, 2.5, [0.5])").unwrap();
+    assert_eq!(
+      captured.text(),
+      "Float(0.3333333333333333)\nFloat(0.333333)\nFloat(2.500000)\nArray([Float(0.5)])\n"
+    );
+  }
+
+  fn method_names(executor: &mut Executor) -> Vec<String> {
+    match run(executor, "Dim m = Methods()") {
+      Ok(Object::Array(names)) => names
+        .iter()
+        .map(|name| match name {
+          Object::String(name) => name.clone(),
+          v => panic!("expected a String, got {:?}", v),
+        })
+        .collect(),
+      r => panic!("expected an Array, got {:?}", r),
+    }
+  }
+
+  #[test]
+  fn methods_lists_only_callable_builtins() {
+    let mut executor = Executor::new();
+    executor.set_timer(Box::new(RecordingTimer(Rc::default())));
+    let names = method_names(&mut executor);
+    assert!(names.contains(&"Print".to_string()));
+    assert!(names.contains(&"Sleep".to_string()));
+    assert_eq!(names.len(), METHODS.len());
+    executor.set_method_enabled("Hash", false);
+    let names = method_names(&mut executor);
+    assert!(names.contains(&"Print".to_string()));
+    assert!(!names.contains(&"Hash".to_string()));
+    assert_eq!(names.len(), METHODS.len() - 1);
+    executor.set_method_enabled("Hash", true);
+    assert!(method_names(&mut executor).contains(&"Hash".to_string()));
+  }
+
+  #[test]
+  fn a_disabled_builtin_is_an_unknown_method() {
+    let mut executor = Executor::new();
+    executor.set_method_enabled("hash", false);
+    assert_eq!(
+      run(&mut executor, "Dim h = Hash(\"a\")"),
+      Err(RuntimeError::UnknownMethod("Hash".to_string()))
+    );
+    assert!(run(&mut executor, "Print(1)").is_ok());
+  }
+
+  #[test]
+  fn sleep_is_only_listed_with_a_timer() {
+    let mut executor = Executor::new();
+    executor.timer = None;
+    let names = method_names(&mut executor);
+    assert!(names.contains(&"Print".to_string()));
+    assert!(!names.contains(&"Sleep".to_string()));
+  }
+
+  #[test]
+  fn a_readonly_run_leaves_the_variables_alone() {
+    let mut executor = Executor::new();
+    run(
+      &mut executor,
+      "Dim x = 1\nDim xs = [1, 2]\nDim h = Heap()\nHeapPush(h, 4)",
+    )
+    .unwrap();
+    let before = executor.state_hash();
+    let program = parse_source(
+      "x = x + 10\nxs = [...xs, 3]\nHeapPush(h, 1)\nDim fresh = 5\nDim result = x",
+      "<test>",
+    )
+    .unwrap();
+    assert_eq!(executor.execute_readonly(&program), Ok(Object::Integer(11)));
+    assert_eq!(executor.state_hash(), before);
+    assert_eq!(executor.get_variable("x"), Some(Object::Integer(1)));
+    assert_eq!(executor.get_variable("fresh"), None);
+    assert_eq!(
+      run(&mut executor, "Dim top = HeapPeek(h)"),
+      Ok(Object::Integer(4))
+    );
+  }
+
+  #[test]
+  fn a_readonly_run_uses_the_same_settings() {
+    let mut executor = Executor::new();
+    executor.set_float_policy(FloatPolicy::Error);
+    executor.set_integral_float_indices(true);
+    let divide = BinaryOperator::DIV;
+    for (source, expected) in [
+      (
+        "Dim x = 1.0 / 0.0",
+        Err(RuntimeError::FloatingPointError { operator: divide }),
+      ),
+      ("Dim x = [5, 6][1.0]", Ok(Object::Integer(6))),
+    ] {
+      let program = parse_source(source, "<test>").unwrap();
+      assert_eq!(executor.execute_readonly(&program), expected);
+      assert_eq!(executor.execute(&program), expected);
+    }
+  }
+
+  #[test]
+  fn a_readonly_run_sleeps_with_the_executors_timer() {
+    let slept = Rc::new(RefCell::new(vec![]));
+    let mut executor = Executor::new();
+    executor.set_timer(Box::new(RecordingTimer(slept.clone())));
+    let program = parse_source("Sleep(60000)", "<test>").unwrap();
+    assert_eq!(executor.execute_readonly(&program), Ok(Object::Unit));
+    assert_eq!(*slept.borrow(), vec![Duration::from_secs(60)]);
+  }
+
+  #[test]
+  fn coverage_numbers_statements_the_same_in_every_run() {
+    let source = "Dim n = 0\nWhile n < 2\n  If n = 0 Then\n    Inc(n)\n  Else\n    Dim a = 1\n    Inc(n)\n  End If\nEnd While\nGuard n = 2 Else\n  Halt(1)\nEnd Guard\nDim d = 4";
+    let compiled = CompiledProgram::compile(source, "<test>").unwrap();
+    let mut executor = Executor::new();
+    executor.set_coverage(true);
+    for _ in 0..2 {
+      compiled.execute(&mut executor).unwrap();
+      let covered: Vec<usize> = executor.covered_statements().iter().cloned().collect();
+      assert_eq!(covered, vec![0, 1, 2, 3, 4, 5, 6, 8]);
+    }
+    run(&mut executor, source).unwrap();
+    let covered: Vec<usize> = executor.covered_statements().iter().cloned().collect();
+    assert_eq!(covered, vec![0, 1, 2, 3, 4, 5, 6, 8]);
+  }
+
+  #[test]
+  fn and_and_or_skip_a_right_operand_that_cannot_change_the_result() {
+    assert_eq!(
+      eval_str("Dim x = true Or (1 / 0 = 0)"),
+      Ok(Object::Boolean(true))
+    );
+    assert_eq!(
+      eval_str("Dim x = false And (1 / 0 = 0)"),
+      Ok(Object::Boolean(false))
+    );
+    assert_eq!(
+      eval_str("Dim x = false And (1 Mod 0 = 0) Or true"),
+      Ok(Object::Boolean(true))
+    );
+    assert_eq!(
+      eval_str("Dim x = false Or (1 / 0 = 0)"),
+      Err(EvalError::Runtime(RuntimeError::DivisionByZero))
+    );
+    assert_eq!(
+      eval_str("Dim x = true And (1 / 0 = 0)"),
+      Err(EvalError::Runtime(RuntimeError::DivisionByZero))
+    );
+  }
 }
@@ -22,15 +22,20 @@ impl<'a> InputStream<'a> {
       range_start: 0,
     };
     l.next();
-    return l;
+    l
   }
 
+  // Lines and columns count from 0, starting at the first character.
   pub fn next(&mut self) {
-    if self.current_char == b'\n' || self.current_char == b'\r' {
-      self.current_line += 1;
-      self.current_column = 0;
-    } else {
-      self.current_column += 1;
+    match self.current_char {
+      // CRLF ends one line, not two; the '\n' that follows begins the next.
+      b'\r' if self.prefetch() == b'\n' => self.current_column += 1,
+      b'\n' | b'\r' => {
+        self.current_line += 1;
+        self.current_column = 0;
+      }
+      _ if 0 < self.read_position => self.current_column += 1,
+      _ => {}
     }
     if self.read_position >= self.input.len() {
       self.current_char = 0;
@@ -45,6 +50,15 @@ impl<'a> InputStream<'a> {
     self.current_char
   }
 
+  // The full (possibly multi-byte) character starting at the current byte.
+  pub fn current_char_lossy(&self) -> char {
+    self
+      .input
+      .get(self.current_position..)
+      .and_then(|s| s.chars().next())
+      .unwrap_or(char::REPLACEMENT_CHARACTER)
+  }
+
   pub fn current_location(&self) -> (String, usize, usize) {
     (
       self.file_name.clone(),
@@ -53,11 +67,11 @@ impl<'a> InputStream<'a> {
     )
   }
 
-  pub fn prefetch(&mut self) -> u8 {
+  pub fn prefetch(&self) -> u8 {
     if self.read_position >= self.input.len() {
-      return 0;
+      0
     } else {
-      return self.input.as_bytes()[self.read_position];
+      self.input.as_bytes()[self.read_position]
     }
   }
 
@@ -65,10 +79,11 @@ impl<'a> InputStream<'a> {
     String::from_utf8(vec![self.current_char]).unwrap()
   }
 
+  // The current character and the one after it; the second becomes current.
   pub fn current_2_to_string(&mut self) -> String {
-    self.start_range();
+    let start = self.current_position;
     self.next();
-    self.range_to_string()
+    self.input[start..self.read_position].to_string()
   }
 
   pub fn start_range(&mut self) {
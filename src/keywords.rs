@@ -1,21 +1,44 @@
 use super::token_kind::TokenKind;
 
+const KEYWORDS: [(&str, TokenKind); 21] = [
+  ("Const", TokenKind::CONST),
+  ("Dim", TokenKind::DIM),
+  ("And", TokenKind::AND),
+  ("Not", TokenKind::NOT),
+  ("Or", TokenKind::OR),
+  ("Xor", TokenKind::XOR),
+  ("Mod", TokenKind::MOD),
+  ("If", TokenKind::IF),
+  ("Then", TokenKind::THEN),
+  ("Else", TokenKind::ELSE),
+  ("End", TokenKind::END),
+  ("For", TokenKind::FOR),
+  ("To", TokenKind::TO),
+  ("Next", TokenKind::NEXT),
+  ("Do", TokenKind::DO),
+  ("Loop", TokenKind::LOOP),
+  ("While", TokenKind::WHILE),
+  ("Exit", TokenKind::EXIT),
+  ("true", TokenKind::TRUE),
+  ("false", TokenKind::FALSE),
+  ("Guard", TokenKind::GUARD),
+];
+
 pub fn get_keyword(ident: &str) -> TokenKind {
-  match ident {
-    "Const" => TokenKind::CONST,
-    "Dim" => TokenKind::DIM,
-    "And" => TokenKind::AND,
-    "Not" => TokenKind::NOT,
-    "Or" => TokenKind::OR,
-    "Xor" => TokenKind::XOR,
-    "Mod" => TokenKind::MOD,
-    "If" => TokenKind::IF,
-    "Then" => TokenKind::THEN,
-    "Else" => TokenKind::ELSE,
-    "End" => TokenKind::END,
-    "For" => TokenKind::FOR,
-    "To" => TokenKind::TO,
-    "Next" => TokenKind::NEXT,
-    _ => TokenKind::IDENT,
+  KEYWORDS
+    .iter()
+    .find(|(keyword, _)| *keyword == ident)
+    .map_or(TokenKind::IDENT, |(_, kind)| *kind)
+}
+
+// Matches CONST, const and Const alike; a keyword comes back with its usual
+// spelling, anything else unchanged.
+pub fn get_keyword_ignore_case(ident: &str) -> (TokenKind, String) {
+  match KEYWORDS
+    .iter()
+    .find(|(keyword, _)| keyword.eq_ignore_ascii_case(ident))
+  {
+    Some((keyword, kind)) => (*kind, keyword.to_string()),
+    None => (TokenKind::IDENT, ident.to_string()),
   }
 }
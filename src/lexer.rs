@@ -1,19 +1,76 @@
 use super::input_stream::InputStream;
-use super::keywords::get_keyword;
+use super::keywords::{get_keyword, get_keyword_ignore_case};
+use super::parse_error::{ParseError, ParseErrorType};
 use super::token::Token;
 use super::token_kind::TokenKind;
 
+// The marker that starts a comment running to the end of the line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommentStyle {
+  DoubleSlash,
+  Hash,
+  DoubleDash,
+}
+
 pub struct Lexer<'a> {
   input_stream: InputStream<'a>,
+  comment_style: CommentStyle,
+  case_insensitive_keywords: bool,
+  // Where the token being scanned begins; tokens report this position.
+  token_start: (String, usize, usize),
+  // When set, a bad token is recorded here and read as ILLEGAL instead.
+  collect_errors: bool,
+  errors: Vec<ParseError>,
 }
 
 impl<'a> Lexer<'a> {
   pub fn new(input_stream: InputStream<'a>) -> Self {
-    Lexer { input_stream }
+    Lexer {
+      input_stream,
+      comment_style: CommentStyle::DoubleSlash,
+      case_insensitive_keywords: false,
+      token_start: (String::new(), 0, 0),
+      collect_errors: false,
+      errors: vec![],
+    }
+  }
+
+  pub fn set_comment_style(&mut self, comment_style: CommentStyle) {
+    self.comment_style = comment_style;
+  }
+
+  // For BASIC-like dialects; identifiers stay case-sensitive.
+  pub fn set_case_insensitive_keywords(&mut self, case_insensitive_keywords: bool) {
+    self.case_insensitive_keywords = case_insensitive_keywords;
+  }
+
+  // The rest of a line with a bad token is skipped, so reading goes on with
+  // the next line.
+  pub fn set_collect_errors(&mut self, collect_errors: bool) {
+    self.collect_errors = collect_errors;
+  }
+
+  pub fn take_errors(&mut self) -> Vec<ParseError> {
+    std::mem::take(&mut self.errors)
+  }
+
+  pub fn next_token(&mut self) -> Result<Token, ParseError> {
+    match self.scan_token() {
+      Err(e) if self.collect_errors => {
+        self.errors.push(e);
+        self.skip_rest_of_line();
+        Ok(self.new_token(TokenKind::ILLEGAL, String::new()))
+      }
+      token => token,
+    }
   }
 
-  pub fn next_token(&mut self) -> Token {
+  fn scan_token(&mut self) -> Result<Token, ParseError> {
     self.skip_whitespace();
+    if self.at_comment() {
+      self.skip_rest_of_line();
+    }
+    self.token_start = self.input_stream.current_location();
     let token;
     match self.input_stream.current() {
       b'\r' => {
@@ -32,7 +89,14 @@ impl<'a> Lexer<'a> {
       b'-' => token = self.new_token_with_1(TokenKind::MINUS),
       b'(' => token = self.new_token_with_1(TokenKind::LPAREN),
       b')' => token = self.new_token_with_1(TokenKind::RPAREN),
-      b'=' => token = self.new_token_with_1(TokenKind::ASSIGN),
+      b'[' => token = self.new_token_with_1(TokenKind::LBRACKET),
+      b']' => token = self.new_token_with_1(TokenKind::RBRACKET),
+      b'.' => token = self.read_ellipsis()?,
+      b'=' => match self.input_stream.prefetch() {
+        b'=' => token = self.new_token_with_2(TokenKind::EQ),
+        _ => token = self.new_token_with_1(TokenKind::ASSIGN),
+      },
+      b'!' if self.input_stream.prefetch() == b'=' => token = self.new_token_with_2(TokenKind::NE),
       b'^' => token = self.new_token_with_1(TokenKind::HAT),
       b'<' => match self.input_stream.prefetch() {
         b'>' => token = self.new_token_with_2(TokenKind::NE),
@@ -43,23 +107,49 @@ impl<'a> Lexer<'a> {
         b'=' => token = self.new_token_with_2(TokenKind::GE),
         _ => token = self.new_token_with_1(TokenKind::GT),
       },
-      b'"' => token = self.parse_string(),
+      b'"' => token = self.parse_string()?,
       0 => token = self.new_token(TokenKind::EOF, String::from("")),
       c => {
         if Self::is_letter(&c) {
           let ident = self.read_identifier();
+          if self.case_insensitive_keywords {
+            let (kind, ident) = get_keyword_ignore_case(&ident);
+            return Ok(self.new_token(kind, ident));
+          }
           let kind = get_keyword(&ident);
-          return self.new_token(kind, ident);
+          return Ok(self.new_token(kind, ident));
         } else if Self::is_digit(&c) {
-          let literal = self.read_number();
-          return self.new_token(TokenKind::INT, literal);
+          return self.read_integer();
         } else {
-          token = self.new_token_with_1(TokenKind::ILLEGAL);
+          return Err(self.unexpected_character());
         }
       }
     };
     self.input_stream.next();
-    token
+    Ok(token)
+  }
+
+  // Leaves the last of the three dots as the current character.
+  fn read_ellipsis(&mut self) -> Result<Token, ParseError> {
+    for _ in 0..2 {
+      if self.input_stream.prefetch() != b'.' {
+        return Err(self.unexpected_character());
+      }
+      self.input_stream.next();
+    }
+    Ok(self.new_token(TokenKind::ELLIPSIS, String::from("...")))
+  }
+
+  fn unexpected_character(&self) -> ParseError {
+    let ch = self.input_stream.current_char_lossy();
+    let (file_name, line, column) = self.input_stream.current_location();
+    ParseError {
+      error_type: ParseErrorType::UnexpectedCharacter { ch },
+      error_message: format!("Unexpected character '{}'", ch),
+      file_name,
+      line,
+      column,
+    }
   }
 
   fn skip_whitespace(&mut self) {
@@ -72,6 +162,22 @@ impl<'a> Lexer<'a> {
     }
   }
 
+  fn at_comment(&mut self) -> bool {
+    let (c, next) = (self.input_stream.current(), self.input_stream.prefetch());
+    match self.comment_style {
+      CommentStyle::DoubleSlash => c == b'/' && next == b'/',
+      CommentStyle::Hash => c == b'#',
+      CommentStyle::DoubleDash => c == b'-' && next == b'-',
+    }
+  }
+
+  // Stops before the line break so a comment still ends its line.
+  fn skip_rest_of_line(&mut self) {
+    while !matches!(self.input_stream.current(), b'\n' | b'\r' | 0) {
+      self.input_stream.next();
+    }
+  }
+
   fn read_identifier(&mut self) -> String {
     self.input_stream.start_range();
     loop {
@@ -84,20 +190,100 @@ impl<'a> Lexer<'a> {
     self.input_stream.range_to_string()
   }
 
-  fn parse_string(&mut self) -> Token {
+  fn parse_string(&mut self) -> Result<Token, ParseError> {
     self.input_stream.next();
-    self.input_stream.start_range();
+    let mut bytes = vec![];
     loop {
       let c = self.input_stream.current();
       if c == b'"' {
         break;
       }
       if c == 0 {
-        return self.new_token_with_1(TokenKind::ILLEGAL);
+        return Ok(self.new_token_with_1(TokenKind::ILLEGAL));
+      }
+      if c == b'\\' {
+        let escape = self.input_stream.current_location();
+        self.input_stream.next();
+        let escaped = match self.input_stream.current() {
+          b'n' => b'\n',
+          b't' => b'\t',
+          b'"' => b'"',
+          b'\\' => b'\\',
+          0 => return Ok(self.new_token_with_1(TokenKind::ILLEGAL)),
+          _ => return Err(self.invalid_escape(escape)),
+        };
+        bytes.push(escaped);
+      } else {
+        bytes.push(c);
       }
       self.input_stream.next();
     }
-    self.new_token_by_range(TokenKind::STRING)
+    // Only ASCII bytes are replaced, so the source's UTF-8 stays valid.
+    let value = String::from_utf8(bytes).expect("escapes keep UTF-8 valid");
+    Ok(self.new_token(TokenKind::STRING, value))
+  }
+
+  fn invalid_escape(&self, (file_name, line, column): (String, usize, usize)) -> ParseError {
+    let ch = self.input_stream.current_char_lossy();
+    ParseError {
+      error_type: ParseErrorType::InvalidEscape { ch },
+      error_message: format!("Unknown escape sequence '\\{}'", ch),
+      file_name,
+      line,
+      column,
+    }
+  }
+
+  fn read_integer(&mut self) -> Result<Token, ParseError> {
+    let (file_name, line, column) = self.input_stream.current_location();
+    let mut literal = self.read_number();
+    if self.input_stream.current() == b'.' && Self::is_digit(&self.input_stream.prefetch()) {
+      return self.read_float((file_name, line, column), literal);
+    }
+    let message = if Self::is_letter(&self.input_stream.current()) {
+      literal.push_str(&self.read_identifier());
+      format!("'{}' is not a valid integer literal", literal)
+    // 2147483648 is let through for the parser to fold into `-2147483648`.
+    } else if literal
+      .parse::<u32>()
+      .map_or(true, |n| n > i32::MIN.unsigned_abs())
+    {
+      format!("'{}' does not fit in a 32-bit integer", literal)
+    } else {
+      return Ok(self.new_token(TokenKind::INT, literal));
+    };
+    Err(ParseError {
+      error_type: ParseErrorType::InvalidIntegerLiteral { literal },
+      error_message: message,
+      file_name,
+      line,
+      column,
+    })
+  }
+
+  fn read_float(
+    &mut self,
+    (file_name, line, column): (String, usize, usize),
+    mut literal: String,
+  ) -> Result<Token, ParseError> {
+    self.input_stream.next();
+    literal.push('.');
+    literal.push_str(&self.read_number());
+    let message = if Self::is_letter(&self.input_stream.current()) {
+      literal.push_str(&self.read_identifier());
+      format!("'{}' is not a valid float literal", literal)
+    } else if !literal.parse::<f64>().is_ok_and(f64::is_finite) {
+      format!("'{}' is too large for a Float", literal)
+    } else {
+      return Ok(self.new_token(TokenKind::FLOAT, literal));
+    };
+    Err(ParseError {
+      error_type: ParseErrorType::InvalidFloatLiteral { literal },
+      error_message: message,
+      file_name,
+      line,
+      column,
+    })
   }
 
   fn read_number(&mut self) -> String {
@@ -114,16 +300,16 @@ impl<'a> Lexer<'a> {
 
   fn is_letter(ch: &u8) -> bool {
     let ch = char::from(*ch);
-    'a' <= ch && ch <= 'z' || 'A' <= ch && ch <= 'Z' || ch == '_'
+    ch.is_ascii_lowercase() || ch.is_ascii_uppercase() || ch == '_'
   }
 
   fn is_digit(ch: &u8) -> bool {
     let ch = char::from(*ch);
-    return '0' <= ch && ch <= '9';
+    ch.is_ascii_digit()
   }
 
   fn new_token(&self, kind: TokenKind, value: String) -> Token {
-    let (file_name, line, column) = self.input_stream.current_location();
+    let (file_name, line, column) = self.token_start.clone();
 
     Token {
       kind,
@@ -143,9 +329,245 @@ impl<'a> Lexer<'a> {
     let value = self.input_stream.current_2_to_string();
     self.new_token(kind, value)
   }
+}
 
-  fn new_token_by_range(&mut self, kind: TokenKind) -> Token {
-    let value = self.input_stream.range_to_string();
-    self.new_token(kind, value)
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn tokens(mut lexer: Lexer) -> Result<Vec<(TokenKind, String)>, ParseError> {
+    let mut tokens = vec![];
+    loop {
+      let token = lexer.next_token()?;
+      if token.kind == TokenKind::EOF {
+        return Ok(tokens);
+      }
+      tokens.push((token.kind, token.value));
+    }
+  }
+
+  fn lex(source: &str) -> Result<Vec<(TokenKind, String)>, ParseError> {
+    lex_with(source, false)
+  }
+
+  fn lex_with(
+    source: &str,
+    case_insensitive_keywords: bool,
+  ) -> Result<Vec<(TokenKind, String)>, ParseError> {
+    let mut lexer = Lexer::new(InputStream::new(source, String::from("<test>")));
+    lexer.set_case_insensitive_keywords(case_insensitive_keywords);
+    tokens(lexer)
+  }
+
+  #[test]
+  fn integer_literals_are_read() {
+    assert_eq!(
+      lex("2147483647"),
+      Ok(vec![(TokenKind::INT, "2147483647".to_string())])
+    );
+  }
+
+  #[test]
+  fn overflowing_integer_literals_are_rejected_where_they_start() {
+    let e = lex("Dim x = 99999999999").unwrap_err();
+    assert_eq!(
+      e.error_type,
+      ParseErrorType::InvalidIntegerLiteral {
+        literal: "99999999999".to_string()
+      }
+    );
+    assert_eq!((e.line, e.column), (0, 8));
+    assert!(lex("2147483649").is_err());
+  }
+
+  #[test]
+  fn the_magnitude_of_the_smallest_integer_is_left_to_the_parser() {
+    assert_eq!(
+      lex("2147483648"),
+      Ok(vec![(TokenKind::INT, "2147483648".to_string())])
+    );
+  }
+
+  #[test]
+  fn a_number_followed_by_letters_is_one_bad_literal() {
+    let e = lex("12abc").unwrap_err();
+    assert_eq!(
+      e.error_type,
+      ParseErrorType::InvalidIntegerLiteral {
+        literal: "12abc".to_string()
+      }
+    );
+  }
+
+  #[test]
+  fn a_line_is_read_as_the_same_token_sequence() {
+    use TokenKind::*;
+    let tokens = lex("If a >= 3 And Not b Then Print(\"x\", 1.5 ^ 2, [a]) // done\n").unwrap();
+    let expected = [
+      (IF, "If"),
+      (IDENT, "a"),
+      (GE, ">="),
+      (INT, "3"),
+      (AND, "And"),
+      (NOT, "Not"),
+      (IDENT, "b"),
+      (THEN, "Then"),
+      (IDENT, "Print"),
+      (LPAREN, "("),
+      (STRING, "x"),
+      (COMMA, ","),
+      (FLOAT, "1.5"),
+      (HAT, "^"),
+      (INT, "2"),
+      (COMMA, ","),
+      (LBRACKET, "["),
+      (IDENT, "a"),
+      (RBRACKET, "]"),
+      (RPAREN, ")"),
+      (EOL, "\n"),
+    ];
+    let expected: Vec<(TokenKind, String)> = expected
+      .iter()
+      .map(|(kind, value)| (*kind, value.to_string()))
+      .collect();
+    assert_eq!(tokens, expected);
+  }
+
+  #[test]
+  fn keywords_can_ignore_case_while_identifiers_keep_it() {
+    let tokens = |source| -> Vec<(TokenKind, String)> { lex_with(source, true).unwrap() };
+    assert_eq!(
+      tokens("CONST Name"),
+      vec![
+        (TokenKind::CONST, "Const".to_string()),
+        (TokenKind::IDENT, "Name".to_string())
+      ]
+    );
+    assert_eq!(tokens("const"), tokens("Const"));
+    assert_eq!(
+      lex("CONST"),
+      Ok(vec![(TokenKind::IDENT, "CONST".to_string())])
+    );
+  }
+
+  fn unexpected(source: &str) -> (ParseErrorType, usize, usize) {
+    let e = lex(source).unwrap_err();
+    (e.error_type, e.line, e.column)
+  }
+
+  #[test]
+  fn unknown_characters_are_reported_with_their_position() {
+    assert_eq!(
+      unexpected("Dim x = @"),
+      (ParseErrorType::UnexpectedCharacter { ch: '@' }, 0, 8)
+    );
+    assert_eq!(
+      unexpected("Dim x = 1\nPrint($x)"),
+      (ParseErrorType::UnexpectedCharacter { ch: '$' }, 1, 6)
+    );
+    assert_eq!(
+      unexpected("é"),
+      (ParseErrorType::UnexpectedCharacter { ch: 'é' }, 0, 0)
+    );
+    assert!(lex("Dim s = \"@ and $\"").is_ok());
+  }
+
+  #[test]
+  fn true_and_false_are_keywords() {
+    assert_eq!(
+      lex("x = true And false").unwrap(),
+      vec![
+        (TokenKind::IDENT, "x".to_string()),
+        (TokenKind::ASSIGN, "=".to_string()),
+        (TokenKind::TRUE, "true".to_string()),
+        (TokenKind::AND, "And".to_string()),
+        (TokenKind::FALSE, "false".to_string()),
+      ]
+    );
+    assert_eq!(lex("truely").unwrap()[0].0, TokenKind::IDENT);
+  }
+
+  #[test]
+  fn string_escapes_are_decoded() {
+    assert_eq!(
+      lex(r#""a\nb\tc\"d\\e""#).unwrap(),
+      vec![(TokenKind::STRING, "a\nb\tc\"d\\e".to_string())]
+    );
+    assert_eq!(
+      lex(r#""bad \q""#).unwrap_err().error_type,
+      ParseErrorType::InvalidEscape { ch: 'q' }
+    );
+  }
+
+  fn lex_comments(
+    source: &str,
+    style: CommentStyle,
+  ) -> Result<Vec<(TokenKind, String)>, ParseError> {
+    let mut lexer = Lexer::new(InputStream::new(source, String::from("<test>")));
+    lexer.set_comment_style(style);
+    tokens(lexer)
+  }
+
+  #[test]
+  fn comments_run_to_the_end_of_the_line() {
+    let expected = vec![
+      (TokenKind::INT, "1".to_string()),
+      (TokenKind::EOL, "\n".to_string()),
+      (TokenKind::INT, "2".to_string()),
+    ];
+    assert_eq!(lex("1 // one\n2").unwrap(), expected);
+    assert_eq!(
+      lex_comments("1 # one\n2", CommentStyle::Hash).unwrap(),
+      expected
+    );
+    assert_eq!(
+      lex_comments("1 -- one\n2", CommentStyle::DoubleDash).unwrap(),
+      expected
+    );
+  }
+
+  #[test]
+  fn other_markers_are_not_comments_by_default() {
+    assert_eq!(
+      lex("# comment").unwrap_err().error_type,
+      ParseErrorType::UnexpectedCharacter { ch: '#' }
+    );
+    assert_eq!(lex("4 / 2").unwrap()[1].0, TokenKind::SLASH);
+    assert_eq!(
+      lex_comments("1 // x", CommentStyle::Hash).unwrap()[1].0,
+      TokenKind::SLASH
+    );
+  }
+
+  #[test]
+  fn tokens_start_where_their_first_character_is() {
+    let mut lexer = Lexer::new(InputStream::new(
+      "Dim x = 10\r\n  Print(x)",
+      String::from("<test>"),
+    ));
+    let mut positions = vec![];
+    loop {
+      let token = lexer.next_token().unwrap();
+      if token.kind == TokenKind::EOF {
+        break;
+      }
+      positions.push((token.value, token.line, token.column));
+    }
+    let expected = [
+      ("Dim", 0, 0),
+      ("x", 0, 4),
+      ("=", 0, 6),
+      ("10", 0, 8),
+      ("\r\n", 0, 10),
+      ("Print", 1, 2),
+      ("(", 1, 7),
+      ("x", 1, 8),
+      (")", 1, 9),
+    ];
+    let expected: Vec<(String, usize, usize)> = expected
+      .iter()
+      .map(|(value, line, column)| (value.to_string(), *line, *column))
+      .collect();
+    assert_eq!(positions, expected);
   }
 }
@@ -0,0 +1,20 @@
+#![allow(clippy::upper_case_acronyms)]
+
+pub mod ast;
+mod builtins;
+pub mod compiled_program;
+pub mod eval;
+pub mod executor;
+pub mod input_stream;
+mod keywords;
+pub mod lexer;
+pub mod object;
+pub mod parse_error;
+pub mod parser;
+pub mod repl;
+pub mod runtime_error;
+pub mod timer;
+pub mod token;
+pub mod token_kind;
+
+pub use eval::{eval_str, EvalError};
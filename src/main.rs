@@ -1,18 +1,9 @@
-mod ast;
-mod executor;
-mod input_stream;
-mod keywords;
-mod lexer;
-mod object;
-mod parse_error;
-mod parser;
-mod runtime_error;
-mod token;
-mod token_kind;
 extern crate log;
-use env_logger;
+use bsharp_lang::repl::Repl;
+use bsharp_lang::{executor, parser, runtime_error};
 use std::env;
 use std::fs::File;
+use std::io;
 use std::io::prelude::*;
 
 fn main() {
@@ -20,6 +11,13 @@ fn main() {
   env_logger::init();
 
   let args: Vec<String> = env::args().collect();
+  // The REPL's stdout carries only the values it prints.
+  if args.len() < 2 {
+    Repl::new()
+      .run(io::stdin().lock(), io::stdout(), io::stderr())
+      .expect("something went wrong reading the input");
+    return;
+  }
   println!("B# version 0.0");
   println!("{:?}", args);
   let src = &args[1];
@@ -30,10 +28,7 @@ fn main() {
     .expect("something went wrong reading the file");
   println!("Sourcecode:\n\n{}", contents);
   let mut e = executor::Executor::new();
-  let i = input_stream::InputStream::new(&contents, String::from(src));
-  let l = lexer::Lexer::new(i);
-  let mut parser = parser::Parser::new(l);
-  let program = parser.parse_program();
+  let program = parser::parse_source(&contents, src);
   match program {
     Ok(p) => {
       println!("{:?}", p);
@@ -42,6 +37,9 @@ fn main() {
         Ok(r) => {
           println!("Result: {}", r);
         }
+        Err(runtime_error::RuntimeError::Halt(code)) => {
+          std::process::exit(code);
+        }
         Err(e) => {
           println!("Execution error: {}", e);
         }
@@ -1,16 +1,26 @@
+use super::ast::BinaryOperator;
 use super::runtime_error::RuntimeError;
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, BinaryHeap};
 use std::fmt;
+use std::rc::Rc;
 
 #[derive(Debug, PartialEq)]
 pub enum RuntimeType {
   Integer,
+  Float,
   Boolean,
   String,
+  Array,
+  Heap,
+  Set,
+  StringBuilder,
   Undefined,
+  Unit,
 }
 impl fmt::Display for RuntimeType {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-    write!(f, "{}", self)
+    write!(f, "{:?}", self)
   }
 }
 
@@ -18,24 +28,140 @@ pub trait TypeOf {
   fn type_of(&self) -> RuntimeType;
 }
 
+pub trait Truthy {
+  fn truthy(&self) -> bool;
+}
+
+// Approximate number of bytes owned on the heap, excluding the value itself.
+pub trait HeapSize {
+  fn heap_size(&self) -> usize;
+}
+
 pub trait Add {
   fn add(&self, offset: i32) -> Result<Object, RuntimeError>;
 }
 
-#[derive(Debug, Clone)]
+// A min-heap of integers, shared between copies until one of them is modified.
+#[derive(Clone, Default)]
+pub struct Heap(Rc<BinaryHeap<Reverse<i32>>>);
+
+impl Heap {
+  pub fn push(&mut self, n: i32) {
+    Rc::make_mut(&mut self.0).push(Reverse(n));
+  }
+
+  pub fn pop(&mut self) -> Option<i32> {
+    Rc::make_mut(&mut self.0).pop().map(|Reverse(n)| n)
+  }
+
+  pub fn peek(&self) -> Option<i32> {
+    self.0.peek().map(|Reverse(n)| *n)
+  }
+
+  pub fn len(&self) -> usize {
+    self.0.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.0.is_empty()
+  }
+
+  pub fn sorted(&self) -> Vec<i32> {
+    let mut values: Vec<i32> = self.0.iter().map(|Reverse(n)| *n).collect();
+    values.sort_unstable();
+    values
+  }
+}
+
+// Lists the values in order rather than in the order of the internal array,
+// which depends on how the heap was built.
+impl fmt::Debug for Heap {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_tuple("Heap").field(&self.sorted()).finish()
+  }
+}
+
+impl PartialEq for Heap {
+  fn eq(&self, other: &Heap) -> bool {
+    self.sorted() == other.sorted()
+  }
+}
+
+// The values that can be stored in a Set.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SetKey {
+  Integer(i32),
+  Boolean(bool),
+  String(String),
+}
+
+impl SetKey {
+  pub fn from_object(value: &Object) -> Result<SetKey, RuntimeError> {
+    match value {
+      Object::Integer(n) => Ok(SetKey::Integer(*n)),
+      Object::Boolean(b) => Ok(SetKey::Boolean(*b)),
+      Object::String(s) => Ok(SetKey::String(s.clone())),
+      _ => Err(RuntimeError::NotHashable(value.type_of())),
+    }
+  }
+
+  pub fn to_object(&self) -> Object {
+    match self {
+      SetKey::Integer(n) => Object::Integer(*n),
+      SetKey::Boolean(b) => Object::Boolean(*b),
+      SetKey::String(s) => Object::String(s.clone()),
+    }
+  }
+}
+
+// Arrays, Heaps and Sets share their storage when cloned and copy it on the
+// first mutation, so passing them around does not copy every element.
+#[derive(Debug, Clone, PartialEq)]
 pub enum Object {
+  // A missing or uninitialized value.
   Undefined,
+  // What a statement or a method without a result produces.
+  Unit,
   Integer(i32),
+  Float(f64),
   Boolean(bool),
   String(String),
+  Array(Rc<Vec<Object>>),
+  Heap(Heap),
+  Set(Rc<BTreeSet<SetKey>>),
+  StringBuilder(String),
 }
 impl fmt::Display for Object {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
     match self {
       Object::Undefined => write!(f, "Undefined"),
+      Object::Unit => write!(f, "Unit"),
       Object::Integer(v) => write!(f, "Integer({})", v),
+      Object::Float(v) => write!(f, "Float({:?})", v),
       Object::Boolean(b) => write!(f, "Boolean({})", b),
       Object::String(s) => write!(f, "String(\"{}\")", s),
+      Object::Array(elements) => {
+        write!(f, "Array([")?;
+        for (i, e) in elements.iter().enumerate() {
+          if 0 < i {
+            write!(f, ", ")?;
+          }
+          write!(f, "{}", e)?;
+        }
+        write!(f, "])")
+      }
+      Object::Heap(heap) => write!(f, "Heap({:?})", heap.sorted()),
+      Object::Set(keys) => {
+        write!(f, "Set([")?;
+        for (i, k) in keys.iter().enumerate() {
+          if 0 < i {
+            write!(f, ", ")?;
+          }
+          write!(f, "{}", k.to_object())?;
+        }
+        write!(f, "])")
+      }
+      Object::StringBuilder(s) => write!(f, "StringBuilder(\"{}\")", s),
     }
   }
 }
@@ -43,9 +169,15 @@ impl TypeOf for Object {
   fn type_of(&self) -> RuntimeType {
     match self {
       Object::Undefined => RuntimeType::Undefined,
+      Object::Unit => RuntimeType::Unit,
       Object::Integer(_) => RuntimeType::Integer,
+      Object::Float(_) => RuntimeType::Float,
       Object::Boolean(_) => RuntimeType::Boolean,
       Object::String(_) => RuntimeType::String,
+      Object::Array(_) => RuntimeType::Array,
+      Object::Heap(_) => RuntimeType::Heap,
+      Object::Set(_) => RuntimeType::Set,
+      Object::StringBuilder(_) => RuntimeType::StringBuilder,
     }
   }
 }
@@ -53,7 +185,13 @@ impl Add for Object {
   fn add(&self, offset: i32) -> Result<Object, RuntimeError> {
     let actual = self.type_of();
     match self {
-      Object::Integer(n) => Ok(Object::Integer(n + offset)),
+      Object::Integer(n) => {
+        n.checked_add(offset)
+          .map(Object::Integer)
+          .ok_or(RuntimeError::IntegerOverflow {
+            operator: BinaryOperator::ADD,
+          })
+      }
       _ => Err(RuntimeError::TypeMismatch {
         expected: RuntimeType::Integer,
         actual,
@@ -61,3 +199,38 @@ impl Add for Object {
     }
   }
 }
+impl Truthy for Object {
+  fn truthy(&self) -> bool {
+    match self {
+      Object::Undefined | Object::Unit => false,
+      Object::Integer(n) => *n != 0,
+      Object::Float(n) => *n != 0.0,
+      Object::Boolean(b) => *b,
+      Object::String(s) => !s.is_empty(),
+      Object::Array(elements) => !elements.is_empty(),
+      Object::Heap(heap) => !heap.is_empty(),
+      Object::Set(keys) => !keys.is_empty(),
+      Object::StringBuilder(s) => !s.is_empty(),
+    }
+  }
+}
+impl HeapSize for Object {
+  fn heap_size(&self) -> usize {
+    match self {
+      Object::String(s) | Object::StringBuilder(s) => s.capacity(),
+      Object::Array(elements) => {
+        elements.capacity() * std::mem::size_of::<Object>()
+          + elements.iter().map(|e| e.heap_size()).sum::<usize>()
+      }
+      Object::Heap(heap) => heap.0.capacity() * std::mem::size_of::<Reverse<i32>>(),
+      Object::Set(keys) => keys
+        .iter()
+        .map(|k| match k {
+          SetKey::String(s) => std::mem::size_of::<SetKey>() + s.capacity(),
+          _ => std::mem::size_of::<SetKey>(),
+        })
+        .sum(),
+      _ => 0,
+    }
+  }
+}
@@ -1,14 +1,35 @@
+use super::token_kind::TokenKind;
 use std::fmt;
 
 #[derive(Debug, PartialEq)]
-#[allow(dead_code)]
 pub enum ParseErrorType {
   InvalidToken,
   Unsupported,
+  AssignmentCountMismatch,
+  ExpectedExpression { after: TokenKind },
+  // Where the unmatched paren is, counting from 0 like ParseError's own line
+  // and column; displayed counting from 1.
+  UnmatchedParen { line: usize, column: usize },
+  NestingTooDeep,
+  UnexpectedCharacter { ch: char },
+  InvalidIntegerLiteral { literal: String },
+  InvalidFloatLiteral { literal: String },
+  InvalidEscape { ch: char },
+  // A boolean spelled in another case than the configured one, e.g. `True`.
+  UnknownLiteral { literal: String },
+  GuardDoesNotExit,
 }
 impl fmt::Display for ParseErrorType {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-    write!(f, "{:?}", self)
+    match self {
+      ParseErrorType::UnmatchedParen { line, column } => write!(
+        f,
+        "UnmatchedParen {{ line: {}, column: {} }}",
+        line + 1,
+        column + 1
+      ),
+      _ => write!(f, "{:?}", self),
+    }
   }
 }
 
@@ -34,3 +55,19 @@ impl fmt::Display for ParseError {
     Ok(())
   }
 }
+
+// What parse_to_json fails with: the source did not parse, or the Program
+// could not be written as JSON.
+#[derive(Debug)]
+pub enum JsonError {
+  Parse(ParseError),
+  Serialize(serde_json::Error),
+}
+impl fmt::Display for JsonError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      JsonError::Parse(e) => write!(f, "{}", e),
+      JsonError::Serialize(e) => write!(f, "Error: {}", e),
+    }
+  }
+}
@@ -1,28 +1,130 @@
 use super::ast::{
-  expression::Expression, program::Program, statement::Statement, BinaryOperator, UnaryOperator,
+  expression::Expression,
+  program::{program_to_json, Program},
+  statement::Statement,
+  BinaryOperator, UnaryOperator,
 };
+use super::input_stream::InputStream;
 use super::lexer::Lexer;
-use super::parse_error::{ParseError, ParseErrorType};
+use super::parse_error::{JsonError, ParseError, ParseErrorType};
 use super::token::Token;
 use super::token_kind::TokenKind;
 
 use log::debug;
+use std::collections::BTreeMap;
+
+type MultipleAssignment = (Vec<String>, Vec<Expression>);
+
+// A level costs several KB of stack in debug builds, so the default stays well
+// inside the 2 MB a spawned thread gets.
+pub(crate) const DEFAULT_MAX_NESTING_DEPTH: usize = 64;
+
+// The single table of tokens for binary operators; how tightly each binds is
+// defined by BinaryOperator::precedence.
+fn binary_operator(kind: TokenKind) -> Option<BinaryOperator> {
+  let operator = match kind {
+    TokenKind::XOR => BinaryOperator::XOR,
+    TokenKind::OR => BinaryOperator::OR,
+    TokenKind::AND => BinaryOperator::AND,
+    TokenKind::ASSIGN => BinaryOperator::EQ,
+    TokenKind::EQ => BinaryOperator::EQ,
+    TokenKind::NE => BinaryOperator::NE,
+    TokenKind::LT => BinaryOperator::LT,
+    TokenKind::GT => BinaryOperator::GT,
+    TokenKind::LE => BinaryOperator::LE,
+    TokenKind::GE => BinaryOperator::GE,
+    TokenKind::PLUS => BinaryOperator::ADD,
+    TokenKind::MINUS => BinaryOperator::SUB,
+    TokenKind::ASTERISK => BinaryOperator::MUL,
+    TokenKind::SLASH => BinaryOperator::DIV,
+    TokenKind::PERCENT => BinaryOperator::MOD,
+    TokenKind::MOD => BinaryOperator::MOD,
+    TokenKind::HAT => BinaryOperator::EXPOTENTIAL,
+    _ => return None,
+  };
+  Some(operator)
+}
 
 pub struct Parser<'a> {
   lexer: Lexer<'a>,
   current_token: Token,
   next_token: Token,
+  previous_token: Option<Token>,
+  do_depth: usize,
+  nesting_depth: usize,
+  max_nesting_depth: usize,
+  boolean_spellings: BTreeMap<String, bool>,
+  operator_aliases: BTreeMap<String, TokenKind>,
+  // Set by parse_program_collecting, which gathers errors here.
+  collecting: bool,
+  errors: Vec<ParseError>,
 }
 
 impl<'a> Parser<'a> {
-  pub fn new(mut lexer: Lexer<'a>) -> Self {
-    let current_token = lexer.next_token();
-    let next_token = lexer.next_token();
-    return Parser {
+  pub fn new(mut lexer: Lexer<'a>) -> Result<Self, ParseError> {
+    let current_token = lexer.next_token()?;
+    let next_token = lexer.next_token()?;
+    Ok(Parser {
       lexer,
       current_token,
       next_token,
-    };
+      previous_token: None,
+      do_depth: 0,
+      nesting_depth: 0,
+      max_nesting_depth: DEFAULT_MAX_NESTING_DEPTH,
+      boolean_spellings: Self::boolean_spellings("true", "false"),
+      operator_aliases: BTreeMap::new(),
+      collecting: false,
+      errors: vec![],
+    })
+  }
+
+  // Dialects spell boolean literals differently, e.g. True/False or yes/no.
+  pub fn set_boolean_spellings(&mut self, true_spelling: &str, false_spelling: &str) {
+    self.boolean_spellings = Self::boolean_spellings(true_spelling, false_spelling);
+    self.reapply_aliases();
+  }
+
+  // Makes an identifier-like word act as another operator or keyword token,
+  // e.g. `mod` for MOD. Symbols cannot be aliased, the lexer splits them.
+  pub fn set_operator_alias(&mut self, alias: &str, kind: TokenKind) {
+    self.operator_aliases.insert(alias.to_string(), kind);
+    self.reapply_aliases();
+  }
+
+  // The two tokens already read were classified before the latest change.
+  fn reapply_aliases(&mut self) {
+    let mut current = self.current_token.clone();
+    self.apply_alias(&mut current);
+    self.current_token = current;
+    let mut next = self.next_token.clone();
+    self.apply_alias(&mut next);
+    self.next_token = next;
+  }
+
+  // The lexer's TRUE and FALSE are the default spellings only.
+  fn apply_alias(&self, token: &mut Token) {
+    if let TokenKind::IDENT | TokenKind::TRUE | TokenKind::FALSE = token.kind {
+      token.kind = match self.boolean_spellings.get(&token.value) {
+        Some(true) => TokenKind::TRUE,
+        Some(false) => TokenKind::FALSE,
+        None => match self.operator_aliases.get(&token.value) {
+          Some(kind) => *kind,
+          None => TokenKind::IDENT,
+        },
+      };
+    }
+  }
+
+  fn boolean_spellings(true_spelling: &str, false_spelling: &str) -> BTreeMap<String, bool> {
+    let mut spellings = BTreeMap::new();
+    spellings.insert(true_spelling.to_string(), true);
+    spellings.insert(false_spelling.to_string(), false);
+    spellings
+  }
+
+  pub fn set_max_nesting_depth(&mut self, max_nesting_depth: usize) {
+    self.max_nesting_depth = max_nesting_depth;
   }
 
   pub fn parse_program(&mut self) -> Result<Program, ParseError> {
@@ -31,6 +133,31 @@ impl<'a> Parser<'a> {
     Ok(Program { statements })
   }
 
+  // Like parse_program, but a statement that fails to parse is skipped to the
+  // end of its line and parsing goes on. Returns the statements that did
+  // parse along with every error, at most one per line, in source order.
+  pub fn parse_program_collecting(&mut self) -> (Program, Vec<ParseError>) {
+    self.collecting = true;
+    self.lexer.set_collect_errors(true);
+    let program = self.parse_program();
+    self.collecting = false;
+    self.lexer.set_collect_errors(false);
+    // The lexer's error says more than the parser's about the ILLEGAL token.
+    let lexer_errors = self.lexer.take_errors();
+    let mut errors: Vec<ParseError> = std::mem::take(&mut self.errors)
+      .into_iter()
+      .filter(|e| lexer_errors.iter().all(|l| l.line != e.line))
+      .collect();
+    errors.extend(lexer_errors);
+    let program = program.unwrap_or_else(|e| {
+      errors.push(e);
+      Program { statements: vec![] }
+    });
+    errors.sort_by_key(|e| (e.line, e.column));
+    errors.dedup_by_key(|e| e.line);
+    (program, errors)
+  }
+
   fn parse_statements(
     &mut self,
     to_stop: fn(&TokenKind) -> bool,
@@ -45,18 +172,44 @@ impl<'a> Parser<'a> {
         );
         break;
       }
-      let s = self.parse_statement()?;
-      statements.push(s);
-      let k = self.current_token.kind;
-      if !(k == TokenKind::EOL || to_stop(&k)) {
-        return Err(self.raise_error(
-          ParseErrorType::InvalidToken,
-          format!("Expected an end keyword of statement, but {}", k),
-        ));
+      match self.parse_terminated_statement(to_stop) {
+        Ok(s) => statements.push(s),
+        Err(e) if self.collecting => {
+          self.errors.push(e);
+          if self.current_token.kind == TokenKind::EOF {
+            break;
+          }
+          while !matches!(self.current_token.kind, TokenKind::EOL | TokenKind::EOF) {
+            self.next_token()?;
+          }
+        }
+        Err(e) => return Err(e),
       }
-      self.next_token();
+      self.next_token()?;
     }
-    return Ok(statements);
+    Ok(statements)
+  }
+
+  fn parse_terminated_statement(
+    &mut self,
+    to_stop: fn(&TokenKind) -> bool,
+  ) -> Result<Statement, ParseError> {
+    let s = self.parse_statement()?;
+    let k = self.current_token.kind;
+    if k == TokenKind::RPAREN {
+      let (line, column) = (self.current_token.line, self.current_token.column);
+      return Err(self.raise_error(
+        ParseErrorType::UnmatchedParen { line, column },
+        String::from("Found RPAREN without a matching '('"),
+      ));
+    }
+    if !(k == TokenKind::EOL || to_stop(&k)) {
+      return Err(self.raise_error(
+        ParseErrorType::InvalidToken,
+        format!("Expected an end keyword of statement, but {}", k),
+      ));
+    }
+    Ok(s)
   }
 
   fn parse_statement(&mut self) -> Result<Statement, ParseError> {
@@ -64,6 +217,10 @@ impl<'a> Parser<'a> {
     let s = match self.current_token.kind {
       TokenKind::IF => self.parse_if_statement()?,
       TokenKind::FOR => self.parse_for_statement()?,
+      TokenKind::DO => self.parse_do_statement()?,
+      TokenKind::WHILE => self.parse_while_statement()?,
+      TokenKind::EXIT => self.parse_exit_statement()?,
+      TokenKind::GUARD => self.parse_guard_statement()?,
       TokenKind::DIM => self.parse_const_assignment_statement()?,
       TokenKind::CONST => self.parse_const_assignment_statement()?,
       TokenKind::EOL => Statement::Empty,
@@ -84,7 +241,7 @@ impl<'a> Parser<'a> {
           format!("Expected IF, but {}", self.current_token.kind),
         ));
       }
-      self.next_token();
+      self.next_token()?;
       let condition = self.parse_expression()?;
       if self.current_token.kind != TokenKind::THEN {
         return Err(self.raise_error(
@@ -92,14 +249,14 @@ impl<'a> Parser<'a> {
           format!("Expected THEN, but {}", self.current_token.kind),
         ));
       }
-      self.next_token();
+      self.next_token()?;
       if self.current_token.kind != TokenKind::EOL {
         return Err(self.raise_error(
           ParseErrorType::InvalidToken,
           format!("Expected EOL, but {}", self.current_token.kind),
         ));
       }
-      self.next_token();
+      self.next_token()?;
       let statements: Vec<Statement> = self.parse_statements(to_stop)?;
 
       if_blocks.push((condition, statements));
@@ -109,7 +266,7 @@ impl<'a> Parser<'a> {
       debug!("*** LOOP {}", self.current_token.kind);
       if self.current_token.kind == TokenKind::END {
         debug!("*** END");
-        self.next_token();
+        self.next_token()?;
         if self.current_token.kind != TokenKind::IF {
           return Err(self.raise_error(
             ParseErrorType::InvalidToken,
@@ -117,7 +274,7 @@ impl<'a> Parser<'a> {
           ));
         }
         debug!("*** IF");
-        self.next_token();
+        self.next_token()?;
         debug!("*** BREAK");
         break;
       }
@@ -127,15 +284,15 @@ impl<'a> Parser<'a> {
           format!("Expected ELSE, but {}", self.current_token.kind),
         ));
       }
-      if 0 < else_statements.len() {
+      if !else_statements.is_empty() {
         return Err(self.raise_error(
           ParseErrorType::InvalidToken,
           format!("Not expected ELSE, but {}", self.current_token.kind),
         ));
       }
-      self.next_token();
+      self.next_token()?;
       if self.current_token.kind == TokenKind::IF {
-        self.next_token();
+        self.next_token()?;
         let c = self.parse_expression()?;
         if self.current_token.kind != TokenKind::THEN {
           return Err(self.raise_error(
@@ -143,14 +300,14 @@ impl<'a> Parser<'a> {
             format!("Expected THEN, but {}", self.current_token.kind),
           ));
         }
-        self.next_token();
+        self.next_token()?;
         if self.current_token.kind != TokenKind::EOL {
           return Err(self.raise_error(
             ParseErrorType::InvalidToken,
             format!("Expected EOL, but {}", self.current_token.kind),
           ));
         }
-        self.next_token();
+        self.next_token()?;
         let statements: Vec<Statement> = self.parse_statements(to_stop)?;
         if_blocks.push((c, statements));
       } else {
@@ -161,7 +318,7 @@ impl<'a> Parser<'a> {
             format!("Expected EOL, but {}", self.current_token.kind),
           ));
         }
-        self.next_token();
+        self.next_token()?;
         debug!("<<< ELSE {}", self.current_token.kind);
         else_statements = self.parse_statements(to_stop)?;
         debug!(">>>");
@@ -185,7 +342,7 @@ impl<'a> Parser<'a> {
       ));
     }
 
-    self.next_token();
+    self.next_token()?;
     if self.current_token.kind != TokenKind::IDENT {
       return Err(self.raise_error(
         ParseErrorType::InvalidToken,
@@ -193,7 +350,7 @@ impl<'a> Parser<'a> {
       ));
     }
     let loop_counter = self.current_token.value.clone();
-    self.next_token();
+    self.next_token()?;
     debug!("*** loop_counter {},{:?}", loop_counter, self.current_token);
     if self.current_token.kind != TokenKind::ASSIGN {
       return Err(self.raise_error(
@@ -201,7 +358,7 @@ impl<'a> Parser<'a> {
         format!("Expected '=', but {}", self.current_token.kind),
       ));
     }
-    self.next_token();
+    self.next_token()?;
     let loop_counter_from = self.parse_expression()?;
     if self.current_token.kind != TokenKind::TO {
       return Err(self.raise_error(
@@ -209,7 +366,7 @@ impl<'a> Parser<'a> {
         format!("Expected To, but {}", self.current_token.kind),
       ));
     }
-    self.next_token();
+    self.next_token()?;
     let loop_counter_to = self.parse_expression()?;
     if self.current_token.kind != TokenKind::EOL {
       return Err(self.raise_error(
@@ -217,7 +374,7 @@ impl<'a> Parser<'a> {
         format!("Expected EOL, but {}", self.current_token.kind),
       ));
     }
-    self.next_token();
+    self.next_token()?;
     let block: Vec<Statement> = self.parse_statements(to_stop)?;
     if self.current_token.kind != TokenKind::NEXT {
       return Err(self.raise_error(
@@ -225,7 +382,7 @@ impl<'a> Parser<'a> {
         format!("Expected Next, but {}", self.current_token.kind),
       ));
     }
-    self.next_token();
+    self.next_token()?;
     Ok(Statement::ForStatement {
       loop_counter,
       loop_counter_from,
@@ -234,12 +391,161 @@ impl<'a> Parser<'a> {
     })
   }
 
+  fn parse_do_statement(&mut self) -> Result<Statement, ParseError> {
+    debug!(">>> parse_do_statement {}", self.current_token.kind);
+
+    let to_stop: fn(&TokenKind) -> bool = |k| *k == TokenKind::LOOP;
+    if self.current_token.kind != TokenKind::DO {
+      return Err(self.raise_error(
+        ParseErrorType::InvalidToken,
+        format!("Expected Do, but {}", self.current_token.kind),
+      ));
+    }
+    self.next_token()?;
+    if self.current_token.kind != TokenKind::EOL {
+      return Err(self.raise_error(
+        ParseErrorType::InvalidToken,
+        format!("Expected EOL, but {}", self.current_token.kind),
+      ));
+    }
+    self.next_token()?;
+    self.do_depth += 1;
+    let block = self.parse_statements(to_stop);
+    self.do_depth -= 1;
+    let block = block?;
+    if self.current_token.kind != TokenKind::LOOP {
+      return Err(self.raise_error(
+        ParseErrorType::InvalidToken,
+        format!("Expected Loop, but {}", self.current_token.kind),
+      ));
+    }
+    self.next_token()?;
+    Ok(Statement::DoLoop { block })
+  }
+
+  /*
+  - `WhileBlock`                ::= "While" `Expression` "EOL"
+                                      `Statements`
+                                    "End" "While"
+  */
+  fn parse_while_statement(&mut self) -> Result<Statement, ParseError> {
+    debug!(">>> parse_while_statement {}", self.current_token.kind);
+    let to_stop: fn(&TokenKind) -> bool = |k| *k == TokenKind::END;
+    self.next_token()?;
+    let condition = self.parse_expression()?;
+    if self.current_token.kind != TokenKind::EOL {
+      return Err(self.raise_error(
+        ParseErrorType::InvalidToken,
+        format!("Expected EOL, but {}", self.current_token.kind),
+      ));
+    }
+    self.next_token()?;
+    let block = self.parse_statements(to_stop)?;
+    self.next_token()?;
+    if self.current_token.kind != TokenKind::WHILE {
+      return Err(self.raise_error(
+        ParseErrorType::InvalidToken,
+        format!("Expected While, but {}", self.current_token.kind),
+      ));
+    }
+    self.next_token()?;
+    Ok(Statement::WhileStatement { condition, block })
+  }
+
+  fn parse_exit_statement(&mut self) -> Result<Statement, ParseError> {
+    debug!(">>> parse_exit_statement {}", self.current_token.kind);
+    self.next_token()?;
+    if self.current_token.kind != TokenKind::DO {
+      return Err(self.raise_error(
+        ParseErrorType::InvalidToken,
+        format!("Expected Do, but {}", self.current_token.kind),
+      ));
+    }
+    if self.do_depth == 0 {
+      return Err(self.raise_error(
+        ParseErrorType::InvalidToken,
+        String::from("Exit Do is only allowed inside Do ... Loop"),
+      ));
+    }
+    self.next_token()?;
+    Ok(Statement::ExitDo)
+  }
+
+  /*
+  - `GuardBlock`                ::= "Guard" `Expression` "Else" "EOL"
+                                      `Statements`
+                                    "End" "Guard"
+  */
+  fn parse_guard_statement(&mut self) -> Result<Statement, ParseError> {
+    debug!(">>> parse_guard_statement {}", self.current_token.kind);
+    let to_stop: fn(&TokenKind) -> bool = |k| *k == TokenKind::END;
+    self.next_token()?;
+    let condition = self.parse_expression()?;
+    if self.current_token.kind != TokenKind::ELSE {
+      return Err(self.raise_error(
+        ParseErrorType::InvalidToken,
+        format!("Expected Else, but {}", self.current_token.kind),
+      ));
+    }
+    self.next_token()?;
+    if self.current_token.kind != TokenKind::EOL {
+      return Err(self.raise_error(
+        ParseErrorType::InvalidToken,
+        format!("Expected EOL, but {}", self.current_token.kind),
+      ));
+    }
+    self.next_token()?;
+    let else_statements = self.parse_statements(to_stop)?;
+    if !Self::diverges(&else_statements) {
+      return Err(self.raise_error(
+        ParseErrorType::GuardDoesNotExit,
+        String::from("The Else block of Guard must end with Exit Do or Halt"),
+      ));
+    }
+    self.next_token()?;
+    if self.current_token.kind != TokenKind::GUARD {
+      return Err(self.raise_error(
+        ParseErrorType::InvalidToken,
+        format!("Expected Guard, but {}", self.current_token.kind),
+      ));
+    }
+    self.next_token()?;
+    Ok(Statement::GuardStatement {
+      condition,
+      else_statements,
+    })
+  }
+
+  // Whether running the statements always leaves the enclosing block.
+  fn diverges(statements: &[Statement]) -> bool {
+    match statements.iter().rev().find(|s| **s != Statement::Empty) {
+      Some(Statement::ExitDo) => true,
+      // Any spelling: with names that ignore case `HALT(1)` halts, and
+      // otherwise it fails as an unknown method, which leaves the block too.
+      Some(Statement::MethodInvocation { identifier, .. }) => {
+        identifier.eq_ignore_ascii_case("Halt")
+      }
+      Some(Statement::IfStatement {
+        if_blocks,
+        else_statements,
+      }) => Self::diverges(else_statements) && if_blocks.iter().all(|(_, b)| Self::diverges(b)),
+      _ => false,
+    }
+  }
+
   /*
   - `ExpressionStatement`       ::= `Assignment` |
+                                    `MultipleAssignment` |
                                     `MethodInvocation`
   */
   fn parse_expression_statement(&mut self) -> Result<Statement, ParseError> {
     debug!(">>> parse_expression_statement {}", self.current_token.kind);
+    if let Some((identifiers, expressions)) = self.parse_multiple_assignment()? {
+      return Ok(Statement::MultipleAssignment {
+        identifiers,
+        expressions,
+      });
+    }
     match self.parse_assignment()? {
       Some((identifier, expression)) => Ok(Statement::Assignment {
         identifier,
@@ -251,11 +557,20 @@ impl<'a> Parser<'a> {
 
   fn parse_const_assignment_statement(&mut self) -> Result<Statement, ParseError> {
     debug!(">>> parse_const_assignment_statement");
-    self.next_token();
+    let constant = self.current_token.kind == TokenKind::CONST;
+    self.next_token()?;
+    if let Some((identifiers, expressions)) = self.parse_multiple_assignment()? {
+      return Ok(Statement::MultipleDeclaration {
+        identifiers,
+        expressions,
+        constant,
+      });
+    }
     match self.parse_assignment()? {
       Some((identifier, expression)) => Ok(Statement::Declaration {
         identifier,
         expression,
+        constant,
       }),
       None => Err(self.raise_error(
         ParseErrorType::InvalidToken,
@@ -272,11 +587,59 @@ impl<'a> Parser<'a> {
     if self.next_token.kind != TokenKind::ASSIGN {
       return Ok(None);
     }
-    self.next_token();
-    self.next_token();
+    self.next_token()?;
+    self.next_token()?;
     let expression = self.parse_expression()?;
 
-    return Ok(Some((identifier, expression)));
+    Ok(Some((identifier, expression)))
+  }
+
+  /*
+  - `MultipleAssignment`        ::= `IdentifierList` "=" `ExpressionList`
+  */
+  fn parse_multiple_assignment(&mut self) -> Result<Option<MultipleAssignment>, ParseError> {
+    if self.current_token.kind != TokenKind::IDENT || self.next_token.kind != TokenKind::COMMA {
+      return Ok(None);
+    }
+    let mut identifiers: Vec<String> = vec![];
+    loop {
+      if self.current_token.kind != TokenKind::IDENT {
+        return Err(self.raise_error(
+          ParseErrorType::InvalidToken,
+          format!("Expected IDENT, but {}", self.current_token.kind),
+        ));
+      }
+      identifiers.push(self.current_token.value.clone());
+      self.next_token()?;
+      match self.current_token.kind {
+        TokenKind::COMMA => self.next_token()?,
+        TokenKind::ASSIGN => break,
+        k => {
+          return Err(self.raise_error(
+            ParseErrorType::InvalidToken,
+            format!("Expected COMMA or '=', but {}", k),
+          ))
+        }
+      }
+    }
+    self.next_token()?;
+    let mut expressions: Vec<Expression> = vec![self.parse_expression()?];
+    while self.current_token.kind == TokenKind::COMMA {
+      self.next_token()?;
+      expressions.push(self.parse_expression()?);
+    }
+    // A single value is unpacked at run time, e.g. `Dim q, r = DivMod(17, 5)`.
+    if expressions.len() != 1 && identifiers.len() != expressions.len() {
+      return Err(self.raise_error(
+        ParseErrorType::AssignmentCountMismatch,
+        format!(
+          "Expected {} values to assign, but {}",
+          identifiers.len(),
+          expressions.len()
+        ),
+      ));
+    }
+    Ok(Some((identifiers, expressions)))
   }
 
   fn parse_method_invocation(&mut self) -> Result<Statement, ParseError> {
@@ -288,227 +651,358 @@ impl<'a> Parser<'a> {
       ));
     }
     let identifier = self.current_token.value.clone();
-    self.next_token();
+    self.next_token()?;
+    let arguments = self.parse_arguments()?;
+    self.next_token()?;
+    Ok(Statement::MethodInvocation {
+      identifier,
+      arguments,
+    })
+  }
+
+  /*
+  - `ArgumentList`              ::= `Expression` |
+                                    `ArgumentList` , `Expression`
+  */
+  fn parse_arguments(&mut self) -> Result<Vec<Expression>, ParseError> {
+    debug!(">>> parse_arguments");
     if self.current_token.kind != TokenKind::LPAREN {
       return Err(self.raise_error(
         ParseErrorType::InvalidToken,
         format!("Expected LPAREN, but {}", self.current_token.kind),
       ));
     }
-    self.next_token();
+    let open = (self.current_token.line, self.current_token.column);
+    self.next_token()?;
     let mut arguments: Vec<Expression> = vec![];
     if self.current_token.kind != TokenKind::RPAREN {
       loop {
-        arguments.push(self.parse_expression()?);
+        arguments.push(self.parse_element()?);
         if self.current_token.kind == TokenKind::RPAREN {
           break;
         }
+        if let TokenKind::EOL | TokenKind::EOF = self.current_token.kind {
+          return Err(self.unmatched_paren_error(open));
+        }
         if self.current_token.kind != TokenKind::COMMA {
           return Err(self.raise_error(
             ParseErrorType::InvalidToken,
             format!("Expected COMMA, but {}", self.current_token.kind),
           ));
         }
-        self.next_token();
+        self.next_token()?;
       }
     }
-    self.next_token();
-    return Ok(Statement::MethodInvocation {
-      identifier,
-      arguments,
-    });
+    Ok(arguments)
   }
 
   /*
   - `Expression`                ::= `LogicalXorExpression`
-  - `LogicalXorExpression`      ::= `LogicalOrExpression` |
-                                    `LogicalXorExpression` "Xor" `LogicalOrExpression`
+  Operator precedence is defined by `BinaryOperator::precedence` instead of one
+  function per grammar level.
   */
   fn parse_expression(&mut self) -> Result<Expression, ParseError> {
     debug!(">>> parse_expression {}", self.current_token.kind);
-    let e = self.parse_logical_or_expression()?;
-    if self.next_token.kind != TokenKind::XOR {
-      return Ok(e);
-    }
-    self.next_token();
-    let right = self.parse_expression()?;
-    Ok(self.binary_operation(&e, BinaryOperator::XOR, &right))
+    self.parse_nested(0)
   }
 
-  fn parse_logical_or_expression(&mut self) -> Result<Expression, ParseError> {
-    debug!(
-      ">>> parse_logical_or_expression {}",
-      self.current_token.kind
-    );
-    let e = self.parse_logical_and_expression()?;
-    if self.next_token.kind != TokenKind::OR {
-      return Ok(e);
+  // Every path that recurses into a nested expression comes through here:
+  // parentheses and arguments via parse_expression, the operand of a prefix
+  // operator and the right operand of a right-associative one.
+  fn parse_nested(&mut self, min_precedence: u8) -> Result<Expression, ParseError> {
+    if self.nesting_depth >= self.max_nesting_depth {
+      let message = format!(
+        "Expressions cannot be nested deeper than {}",
+        self.max_nesting_depth
+      );
+      return Err(self.raise_error(ParseErrorType::NestingTooDeep, message));
     }
-    self.next_token();
-    let right = self.parse_logical_or_expression()?;
-    Ok(self.binary_operation(&e, BinaryOperator::OR, &right))
+    self.nesting_depth += 1;
+    let e = self.parse_binary_expression(min_precedence);
+    self.nesting_depth -= 1;
+    e
   }
 
-  fn parse_logical_and_expression(&mut self) -> Result<Expression, ParseError> {
+  // Precedence climbing: folds every operator binding at least as tightly as
+  // `min_precedence` into the operand on its left.
+  fn parse_binary_expression(&mut self, min_precedence: u8) -> Result<Expression, ParseError> {
     debug!(
-      ">>> parse_logical_and_expression {}",
-      self.current_token.kind
+      ">>> parse_binary_expression {} {}",
+      min_precedence, self.current_token.kind
     );
-    let e = self.parse_logical_not_expression()?;
-    if self.next_token.kind != TokenKind::AND {
-      return Ok(e);
+    let mut left = self.parse_prefix_expression()?;
+    while let Some(operator) = binary_operator(self.current_token.kind) {
+      let precedence = operator.precedence();
+      if precedence < min_precedence {
+        break;
+      }
+      self.next_token()?;
+      let right = if operator.is_right_associative() {
+        self.parse_nested(precedence)?
+      } else {
+        self.parse_binary_expression(precedence + 1)?
+      };
+      left = self.binary_operation(left, operator, right);
     }
-    self.next_token();
-    let right = self.parse_logical_and_expression()?;
-    Ok(self.binary_operation(&e, BinaryOperator::AND, &right))
+    Ok(left)
   }
 
-  fn parse_logical_not_expression(&mut self) -> Result<Expression, ParseError> {
-    debug!(
-      ">>> parse_logical_not_expression {}",
-      self.current_token.kind
-    );
-    if self.next_token.kind != TokenKind::NOT {
-      return self.parse_equality_expression();
+  /*
+  - `LogicalNotExpression`      ::= "Not" `LogicalNotExpression` |
+                                    `EqualityExpression`
+  - `UnaryExpression`           ::= "+" `UnaryExpression` |
+                                    "-" `UnaryExpression` |
+                                    `Exponential`
+  */
+  fn parse_prefix_expression(&mut self) -> Result<Expression, ParseError> {
+    debug!(">>> parse_prefix_expression {}", self.current_token.kind);
+    let op = match self.current_token.kind {
+      TokenKind::NOT => UnaryOperator::NOT,
+      TokenKind::PLUS => UnaryOperator::POSITIVE,
+      TokenKind::MINUS => UnaryOperator::NEGATIVE,
+      _ => return self.parse_primary(),
+    };
+    self.next_token()?;
+    // 2147483648 only fits in an Integer once it is negated.
+    if matches!(op, UnaryOperator::NEGATIVE)
+      && self.current_token.kind == TokenKind::INT
+      && self.current_token.value.parse::<u32>() == Ok(i32::MIN.unsigned_abs())
+      && !matches!(self.next_token.kind, TokenKind::HAT | TokenKind::LBRACKET)
+    {
+      self.next_token()?;
+      return Ok(Expression::Integer(i32::MIN));
     }
-    self.next_token();
-    let e = self.parse_equality_expression()?;
-    Ok(self.unary_operation(UnaryOperator::NOT, &e))
+    let e = self.parse_nested(op.precedence())?;
+    // `-5` is a literal of its own; `-a` and `-2 ^ 2` stay unary.
+    match (&op, &e) {
+      (UnaryOperator::NEGATIVE, Expression::Integer(n)) if *n != i32::MIN => {
+        return Ok(Expression::Integer(-n))
+      }
+      (UnaryOperator::NEGATIVE, Expression::Float(n)) => return Ok(Expression::Float(-n)),
+      _ => {}
+    }
+    Ok(self.unary_operation(op, e))
+  }
+
+  fn parse_primary(&mut self) -> Result<Expression, ParseError> {
+    debug!(">>> parse_primary {}", self.current_token.kind);
+    let e = match self.current_token.kind {
+      // `If(condition, a, b)` is the conditional builtin, not an If block.
+      TokenKind::IDENT | TokenKind::IF if self.next_token.kind == TokenKind::LPAREN => {
+        let identifier = self.current_token.value.clone();
+        self.next_token()?;
+        let arguments = self.parse_arguments()?;
+        Expression::MethodInvocation {
+          identifier,
+          arguments,
+        }
+      }
+      TokenKind::IDENT => {
+        if let Some(e) = self.misspelled_boolean_error() {
+          return Err(e);
+        }
+        Expression::Identifier(self.current_token.value.clone())
+      }
+      TokenKind::TRUE => Expression::Boolean(true),
+      TokenKind::FALSE => Expression::Boolean(false),
+      TokenKind::INT => self.parse_integer()?,
+      TokenKind::FLOAT => self.parse_float()?,
+      TokenKind::STRING => Expression::String(self.current_token.value.clone()),
+      TokenKind::LPAREN => self.parse_grouped_expression()?,
+      TokenKind::LBRACKET => self.parse_array_literal()?,
+      _ => return Err(self.expected_expression_error()),
+    };
+    self.next_token()?;
+    self.parse_array_access(e)
   }
 
-  fn parse_equality_expression(&mut self) -> Result<Expression, ParseError> {
-    debug!(">>> parse_equality_expression {}", self.current_token.kind);
-    let e = self.parse_additive_expression()?;
-    let op;
-    match self.current_token.kind {
-      TokenKind::ASSIGN => op = BinaryOperator::EQ,
-      TokenKind::NE => op = BinaryOperator::NE,
-      TokenKind::LT => op = BinaryOperator::LT,
-      TokenKind::GT => op = BinaryOperator::GT,
-      TokenKind::LE => op = BinaryOperator::LE,
-      TokenKind::GE => op = BinaryOperator::GE,
-      _ => return Ok(e),
+  /*
+  - `ArrayLiteral`              ::= [ `ArgumentList`? ]
+  */
+  fn parse_array_literal(&mut self) -> Result<Expression, ParseError> {
+    debug!(">>> parse_array_literal");
+    self.next_token()?;
+    let mut elements = vec![];
+    while self.current_token.kind != TokenKind::RBRACKET {
+      elements.push(self.parse_element()?);
+      match self.current_token.kind {
+        TokenKind::RBRACKET => {}
+        TokenKind::COMMA => self.next_token()?,
+        k => {
+          return Err(self.raise_error(
+            ParseErrorType::InvalidToken,
+            format!("Expected COMMA or RBRACKET, but {}", k),
+          ))
+        }
+      }
     }
-    self.next_token();
-    let right = self.parse_equality_expression()?;
-    Ok(self.binary_operation(&e, op, &right))
+    Ok(Expression::Array(elements))
   }
 
-  fn parse_additive_expression(&mut self) -> Result<Expression, ParseError> {
-    debug!(">>> parse_additive_expression {}", self.current_token.kind);
-    let e = self.parse_multiplicative_expression()?;
-    let op;
-    match self.current_token.kind {
-      TokenKind::PLUS => op = BinaryOperator::ADD,
-      TokenKind::MINUS => op = BinaryOperator::SUB,
-      _ => return Ok(e),
+  /*
+  - `Element`                   ::= `Expression` | "..." `Expression`
+  */
+  fn parse_element(&mut self) -> Result<Expression, ParseError> {
+    if self.current_token.kind != TokenKind::ELLIPSIS {
+      return self.parse_expression();
     }
-    self.next_token();
-    let right = self.parse_additive_expression()?;
-    Ok(self.binary_operation(&e, op, &right))
+    self.next_token()?;
+    Ok(Expression::Spread(Box::new(self.parse_expression()?)))
   }
 
-  fn parse_multiplicative_expression(&mut self) -> Result<Expression, ParseError> {
-    debug!(
-      ">>> parse_multiplicative_expression {}",
-      self.current_token.kind
-    );
-    let e = self.parse_unary_expression()?;
-    let op;
-    match self.current_token.kind {
-      TokenKind::ASTERISK => op = BinaryOperator::MUL,
-      TokenKind::SLASH => op = BinaryOperator::DIV,
-      TokenKind::PERCENT => op = BinaryOperator::MOD,
-      TokenKind::MOD => op = BinaryOperator::MOD,
-      _ => return Ok(e),
-    }
-    self.next_token();
-    let right = self.parse_unary_expression()?;
-    Ok(self.binary_operation(&e, op, &right))
-  }
-
-  fn parse_unary_expression(&mut self) -> Result<Expression, ParseError> {
-    debug!(">>> parse_unary_expression {}", self.current_token.kind);
-    let op;
-    match self.current_token.kind {
-      TokenKind::PLUS => op = UnaryOperator::POSITIVE,
-      TokenKind::MINUS => op = UnaryOperator::NEGATIVE,
-      _ => {
-        return Ok(self.parse_exponential_expression()?);
+  /*
+  - `ArrayAccess`               ::= `Primary` [ `Expression`]
+  */
+  fn parse_array_access(&mut self, mut e: Expression) -> Result<Expression, ParseError> {
+    while self.current_token.kind == TokenKind::LBRACKET {
+      debug!(">>> parse_array_access");
+      self.next_token()?;
+      let index = self.parse_expression()?;
+      if self.current_token.kind != TokenKind::RBRACKET {
+        return Err(self.raise_error(
+          ParseErrorType::InvalidToken,
+          format!("Expected RBRACKET, but {}", self.current_token.kind),
+        ));
       }
+      self.next_token()?;
+      e = Expression::Index {
+        expression: Box::new(e),
+        index: Box::new(index),
+      };
     }
-    self.next_token();
-    let e = self.parse_exponential_expression()?;
-    Ok(self.unary_operation(op, &e))
+    Ok(e)
   }
 
-  fn parse_exponential_expression(&mut self) -> Result<Expression, ParseError> {
-    debug!(
-      ">>> parse_exponential_expression {}",
-      self.current_token.kind
-    );
-    let e = self.parse_primary()?;
-    if self.next_token.kind != TokenKind::HAT {
-      return Ok(e);
+  // The lexer already rejects bad literals; this keeps the parser from
+  // panicking on token streams that did not come from it.
+  fn parse_integer(&mut self) -> Result<Expression, ParseError> {
+    match self.current_token.value.parse::<i32>() {
+      Ok(n) => Ok(Expression::Integer(n)),
+      Err(_) => {
+        let literal = self.current_token.value.clone();
+        let message = format!("'{}' does not fit in a 32-bit integer", literal);
+        Err(self.raise_error(ParseErrorType::InvalidIntegerLiteral { literal }, message))
+      }
     }
-    self.next_token();
-    let right = self.parse_exponential_expression()?;
-    Ok(self.binary_operation(&e, BinaryOperator::EXPOTENTIAL, &right))
   }
 
-  fn parse_primary(&mut self) -> Result<Expression, ParseError> {
-    debug!(">>> parse_primary {}", self.current_token.kind);
-    let e = match self.current_token.kind {
-      TokenKind::IDENT => Expression::Identifier(self.current_token.value.clone()),
-      TokenKind::INT => Expression::Integer(self.current_token.value.parse::<i32>().unwrap()),
-      TokenKind::STRING => Expression::String(self.current_token.value.clone()),
-      TokenKind::LPAREN => self.parse_grouped_expression()?,
+  fn parse_float(&mut self) -> Result<Expression, ParseError> {
+    match self.current_token.value.parse::<f64>() {
+      Ok(n) if n.is_finite() => Ok(Expression::Float(n)),
       _ => {
-        return Err(self.raise_error(
-          ParseErrorType::InvalidToken,
-          format!(
-            "Expected IDENT, INT, LPAREN, but {}",
-            self.current_token.kind
-          ),
-        ))
+        let literal = self.current_token.value.clone();
+        let message = format!("'{}' is not a valid float literal", literal);
+        Err(self.raise_error(ParseErrorType::InvalidFloatLiteral { literal }, message))
       }
-    };
-    self.next_token();
-    Ok(e)
+    }
   }
 
   fn binary_operation(
     &mut self,
-    left: &Expression,
+    left: Expression,
     operator: BinaryOperator,
-    right: &Expression,
+    right: Expression,
   ) -> Expression {
     Expression::Binary {
-      left: Box::new(left.clone()),
+      left: Box::new(left),
       operator,
-      right: Box::new(right.clone()),
+      right: Box::new(right),
     }
   }
 
-  fn unary_operation(&mut self, operator: UnaryOperator, expression: &Expression) -> Expression {
+  fn unary_operation(&mut self, operator: UnaryOperator, expression: Expression) -> Expression {
     Expression::Unary {
       operator,
-      expression: Box::new(expression.clone()),
+      expression: Box::new(expression),
     }
   }
 
+  /*
+  - `GroupedExpression`         ::= ( `Expression` ) |
+                                    ( `ExpressionList` )
+  */
   fn parse_grouped_expression(&mut self) -> Result<Expression, ParseError> {
     debug!(">>> parse_grouped_expression");
-    self.next_token();
-    let e = self.parse_expression()?;
-    if self.current_token.kind == TokenKind::RPAREN {
-      Ok(e)
+    let open = (self.current_token.line, self.current_token.column);
+    self.next_token()?;
+    let mut expressions = vec![self.parse_expression()?];
+    while self.current_token.kind == TokenKind::COMMA {
+      self.next_token()?;
+      expressions.push(self.parse_expression()?);
+    }
+    if self.current_token.kind != TokenKind::RPAREN {
+      return Err(self.unmatched_paren_error(open));
+    }
+    if expressions.len() == 1 {
+      Ok(expressions.remove(0))
     } else {
-      Err(self.raise_error(
-        ParseErrorType::InvalidToken,
-        format!("Expected RPAREN, but {}", self.current_token.kind),
-      ))
+      Ok(Expression::Sequence(expressions))
+    }
+  }
+
+  // `open` is the position of the '(' that was never closed.
+  fn unmatched_paren_error(&mut self, open: (usize, usize)) -> ParseError {
+    let (line, column) = open;
+    let message = format!(
+      "Expected RPAREN for '(' at line {}, column {}, but {}",
+      line + 1,
+      column + 1,
+      self.current_token.kind
+    );
+    self.raise_error(ParseErrorType::UnmatchedParen { line, column }, message)
+  }
+
+  // `True` under the default `true`/`false` would otherwise be read as an
+  // undefined variable and only fail at run time.
+  fn misspelled_boolean_error(&mut self) -> Option<ParseError> {
+    let literal = self.current_token.value.clone();
+    let (spelling, _) = self
+      .boolean_spellings
+      .iter()
+      .find(|(spelling, _)| spelling.eq_ignore_ascii_case(&literal))?;
+    let message = format!(
+      "'{}' is not a boolean literal; did you mean '{}'? Other spellings can be enabled with Parser::set_boolean_spellings",
+      literal, spelling
+    );
+    Some(self.raise_error(ParseErrorType::UnknownLiteral { literal }, message))
+  }
+
+  fn expected_expression_error(&mut self) -> ParseError {
+    let after = match &self.previous_token {
+      Some(t) => t.kind,
+      None => TokenKind::EOL,
+    };
+    if self.current_token.kind.is_operator() {
+      let message = format!(
+        "Expected expression before operator '{}'",
+        self.current_token.value
+      );
+      return self.raise_error(ParseErrorType::ExpectedExpression { after }, message);
+    }
+    if after.is_operator() {
+      let operator = self
+        .previous_token
+        .as_ref()
+        .map_or("", |t| t.value.as_str());
+      let message = format!(
+        "Expected expression after operator '{}', but {}",
+        operator, self.current_token.kind
+      );
+      return self.raise_error(ParseErrorType::ExpectedExpression { after }, message);
+    }
+    // A trailing comma in an argument or assignment list, e.g. `Print(1,)`.
+    if after == TokenKind::COMMA {
+      let message = format!(
+        "Expected expression after ',', but {}",
+        self.current_token.kind
+      );
+      return self.raise_error(ParseErrorType::ExpectedExpression { after }, message);
     }
+    let message = format!(
+      "Expected IDENT, INT, LPAREN, but {}",
+      self.current_token.kind
+    );
+    self.raise_error(ParseErrorType::InvalidToken, message)
   }
 
   fn raise_error(&mut self, error_type: ParseErrorType, error_message: String) -> ParseError {
@@ -522,9 +1016,608 @@ impl<'a> Parser<'a> {
     }
   }
 
-  fn next_token(&mut self) {
-    self.current_token = self.next_token.clone();
-    self.next_token = self.lexer.next_token();
+  // Tokens are moved along the window instead of cloned.
+  fn next_token(&mut self) -> Result<(), ParseError> {
+    let mut next = self.lexer.next_token()?;
+    self.apply_alias(&mut next);
+    let current = std::mem::replace(&mut self.next_token, next);
+    self.previous_token = Some(std::mem::replace(&mut self.current_token, current));
     debug!("next_token: {}", self.current_token.kind);
+    Ok(())
+  }
+}
+
+pub fn parse_source(source: &str, file_name: &str) -> Result<Program, ParseError> {
+  let input_stream = InputStream::new(source, String::from(file_name));
+  let mut parser = Parser::new(Lexer::new(input_stream))?;
+  parser.parse_program()
+}
+
+pub fn parse_source_collecting(source: &str, file_name: &str) -> (Program, Vec<ParseError>) {
+  let input_stream = InputStream::new(source, String::from(file_name));
+  let mut lexer = Lexer::new(input_stream);
+  lexer.set_collect_errors(true);
+  match Parser::new(lexer) {
+    Ok(mut parser) => parser.parse_program_collecting(),
+    Err(e) => (Program { statements: vec![] }, vec![e]),
+  }
+}
+
+pub fn programs_equivalent(a: &str, b: &str) -> Result<bool, ParseError> {
+  let a = parse_source(a, "<left>")?;
+  let b = parse_source(b, "<right>")?;
+  Ok(a == b)
+}
+
+pub fn parse_to_json(source: &str) -> Result<String, JsonError> {
+  let program = parse_source(source, "<json>").map_err(JsonError::Parse)?;
+  program_to_json(&program).map_err(JsonError::Serialize)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::eval::eval_str;
+  use crate::executor::Executor;
+  use crate::object::Object;
+  use crate::runtime_error::RuntimeError;
+
+  fn error_type(source: &str) -> ParseErrorType {
+    parse_source(source, "<test>").unwrap_err().error_type
+  }
+
+  #[test]
+  fn thousands_of_nested_parentheses_are_too_deep() {
+    let source = format!("Dim x = {}1{}", "(".repeat(5000), ")".repeat(5000));
+    assert_eq!(error_type(&source), ParseErrorType::NestingTooDeep);
+  }
+
+  #[test]
+  fn nesting_up_to_the_default_limit_runs() {
+    let depth = DEFAULT_MAX_NESTING_DEPTH - 1;
+    let source = format!("Dim x = {}1{}", "(".repeat(depth), ")".repeat(depth));
+    assert_eq!(eval_str(&source), Ok(Object::Integer(1)));
+    let source = format!("Dim x = {}1", "- ".repeat(depth));
+    assert_eq!(eval_str(&source), Ok(Object::Integer(-1)));
+  }
+
+  #[test]
+  fn long_prefix_chains_are_too_deep() {
+    let minus = format!("Print({}x)", "- ".repeat(20000));
+    assert_eq!(error_type(&minus), ParseErrorType::NestingTooDeep);
+    let not = format!("Dim x = {}true", "Not ".repeat(20000));
+    assert_eq!(error_type(&not), ParseErrorType::NestingTooDeep);
+  }
+
+  #[test]
+  fn long_right_associative_chains_are_too_deep() {
+    let source = format!("Dim x = 2{}", " ^ 2".repeat(20000));
+    assert_eq!(error_type(&source), ParseErrorType::NestingTooDeep);
+  }
+
+  #[test]
+  fn the_nesting_limit_is_configurable() {
+    let input_stream = InputStream::new("Dim x = ((1))", String::from("<test>"));
+    let mut parser = Parser::new(Lexer::new(input_stream)).unwrap();
+    parser.set_max_nesting_depth(2);
+    let e = parser.parse_program().unwrap_err();
+    assert_eq!(e.error_type, ParseErrorType::NestingTooDeep);
+  }
+
+  #[test]
+  fn the_smallest_integer_can_be_written() {
+    assert_eq!(
+      eval_str("Dim x = -2147483648"),
+      Ok(Object::Integer(i32::MIN))
+    );
+    assert_eq!(
+      eval_str("Dim x = 1 - -2147483648 + 0").unwrap_err(),
+      crate::eval::EvalError::Runtime(crate::runtime_error::RuntimeError::IntegerOverflow {
+        operator: BinaryOperator::SUB
+      })
+    );
+    assert!(matches!(
+      error_type("Dim x = 2147483648"),
+      ParseErrorType::InvalidIntegerLiteral { .. }
+    ));
+    assert!(matches!(
+      error_type("Dim x = -2147483648 ^ 2"),
+      ParseErrorType::InvalidIntegerLiteral { .. }
+    ));
+  }
+
+  #[test]
+  fn a_ten_thousand_term_chain_evaluates_to_its_sum() {
+    let source = format!("Dim x = 1{}", " + 1".repeat(9999));
+    assert_eq!(eval_str(&source), Ok(Object::Integer(10000)));
+    let source = format!("Dim x = 0{}", " + 2 * 3 - 4".repeat(3333));
+    assert_eq!(eval_str(&source), Ok(Object::Integer(2 * 3333)));
+  }
+
+  #[test]
+  fn an_unclosed_paren_is_reported_where_it_opens() {
+    let e = parse_source("Dim x = 1\nDim y = (1 + 2", "<test>").unwrap_err();
+    assert_eq!(
+      e.error_type,
+      ParseErrorType::UnmatchedParen { line: 1, column: 8 }
+    );
+    assert!(e.error_message.contains("line 2, column 9"));
+    assert!(e
+      .to_string()
+      .contains("UnmatchedParen { line: 2, column: 9 }"));
+  }
+
+  #[test]
+  fn a_stray_closing_paren_is_reported_where_it_is() {
+    assert_eq!(
+      error_type("Print(1 + 2))"),
+      ParseErrorType::UnmatchedParen {
+        line: 0,
+        column: 12
+      }
+    );
+    assert_eq!(
+      error_type("Dim x = 1 + 2)"),
+      ParseErrorType::UnmatchedParen {
+        line: 0,
+        column: 13
+      }
+    );
+  }
+
+  fn parse_with_booleans(source: &str, spellings: (&str, &str)) -> Result<Program, ParseError> {
+    let mut parser = Parser::new(Lexer::new(InputStream::new(source, "<test>".to_string())))?;
+    parser.set_boolean_spellings(spellings.0, spellings.1);
+    parser.parse_program()
+  }
+
+  fn declared(value: bool) -> Program {
+    Program {
+      statements: vec![Statement::Declaration {
+        identifier: "x".to_string(),
+        expression: Expression::Boolean(value),
+        constant: false,
+      }],
+    }
+  }
+
+  #[test]
+  fn booleans_can_be_spelled_per_dialect() {
+    assert_eq!(
+      parse_with_booleans("Dim x = True", ("True", "False")),
+      Ok(declared(true))
+    );
+    assert_eq!(
+      parse_with_booleans("Dim x = no", ("yes", "no")),
+      Ok(declared(false))
+    );
+    assert_eq!(parse_source("Dim x = false", "<test>"), Ok(declared(false)));
+  }
+
+  #[test]
+  fn a_boolean_in_the_wrong_case_is_an_unknown_literal() {
+    let e = parse_source("Dim x = True", "<test>").unwrap_err();
+    assert_eq!(
+      e.error_type,
+      ParseErrorType::UnknownLiteral {
+        literal: "True".to_string()
+      }
+    );
+    assert!(e.error_message.contains("set_boolean_spellings"));
+    let e = parse_with_booleans("Dim x = true", ("True", "False")).unwrap_err();
+    assert_eq!(
+      e.error_type,
+      ParseErrorType::UnknownLiteral {
+        literal: "true".to_string()
+      }
+    );
+  }
+
+  #[test]
+  fn other_boolean_spellings_are_identifiers_by_default() {
+    assert_eq!(
+      eval_str("Dim x = yes"),
+      Err(crate::eval::EvalError::Runtime(
+        crate::runtime_error::RuntimeError::UndefinedVariable("yes".to_string())
+      ))
+    );
+  }
+
+  #[test]
+  fn a_multiple_declaration_needs_one_value_per_name() {
+    assert_eq!(
+      error_type("Dim a, b = 1, 2, 3"),
+      ParseErrorType::AssignmentCountMismatch
+    );
+    assert_eq!(
+      error_type("a, b, c = 1, 2"),
+      ParseErrorType::AssignmentCountMismatch
+    );
+  }
+
+  #[test]
+  fn exit_do_outside_a_loop_is_rejected() {
+    assert!(parse_source("Exit Do", "<test>").is_err());
+    assert!(parse_source("Do\nExit Do\nLoop", "<test>").is_ok());
+  }
+
+  #[test]
+  fn commas_in_parentheses_make_a_sequence_but_not_in_calls() {
+    let program = parse_source("Dim x = (1, 2)\nPrint(1, 2)", "<test>").unwrap();
+    assert_eq!(
+      program.statements[0],
+      Statement::Declaration {
+        identifier: "x".to_string(),
+        expression: Expression::Sequence(vec![Expression::Integer(1), Expression::Integer(2)]),
+        constant: false,
+      }
+    );
+    assert_eq!(
+      program.statements[1],
+      Statement::MethodInvocation {
+        identifier: "Print".to_string(),
+        arguments: vec![Expression::Integer(1), Expression::Integer(2)],
+      }
+    );
+  }
+
+  #[test]
+  fn programs_are_compared_by_their_trees() {
+    assert_eq!(
+      programs_equivalent("Dim x = 2+3", "Dim  x = 2 + 3 // five"),
+      Ok(true)
+    );
+    assert_eq!(programs_equivalent("Dim x = 2+3", "Dim x = 3+2"), Ok(false));
+    assert_eq!(
+      programs_equivalent("Dim x = (2 * 3)", "Dim x = 2 * 3"),
+      Ok(true)
+    );
+    assert!(programs_equivalent("Dim x = 2+", "Dim x = 2").is_err());
+  }
+
+  #[test]
+  fn parse_to_json_round_trips_to_the_same_program() {
+    let source = "Dim a = [1, 2.5, \"s\", true]\nWhile a[0] < 3\n  a = [a[0] + 1]\nEnd While";
+    let json = parse_to_json(source).unwrap();
+    assert_eq!(
+      crate::ast::program::program_from_json(&json).unwrap(),
+      parse_source(source, "<test>").unwrap()
+    );
+    assert!(matches!(
+      parse_to_json("Dim a = "),
+      Err(JsonError::Parse(ParseError {
+        error_type: ParseErrorType::ExpectedExpression { .. },
+        ..
+      }))
+    ));
+  }
+
+  #[test]
+  fn parse_to_json_reports_a_program_too_deep_for_json() {
+    // Nested blocks are not limited by the parser, so this needs more stack
+    // than a test thread has.
+    let source = format!(
+      "{}Dim x = {}1{}\n{}",
+      "While true\n".repeat(150),
+      "[".repeat(40),
+      "]".repeat(40),
+      "End While\n".repeat(150)
+    );
+    let result = std::thread::Builder::new()
+      .stack_size(16 << 20)
+      .spawn(move || matches!(parse_to_json(&source), Err(JsonError::Serialize(_))))
+      .unwrap()
+      .join()
+      .unwrap();
+    assert!(result);
+  }
+
+  #[test]
+  fn a_dangling_operator_names_the_operator() {
+    let error = parse_source("Dim x = 2 +\n", "<test>").unwrap_err();
+    assert_eq!(
+      error.error_type,
+      ParseErrorType::ExpectedExpression {
+        after: TokenKind::PLUS
+      }
+    );
+    assert!(error
+      .error_message
+      .starts_with("Expected expression after operator '+'"));
+    let error = parse_source("Dim x = * 3\n", "<test>").unwrap_err();
+    assert_eq!(
+      error.error_type,
+      ParseErrorType::ExpectedExpression {
+        after: TokenKind::ASSIGN
+      }
+    );
+    assert_eq!(
+      error.error_message,
+      "Expected expression before operator '*'"
+    );
+  }
+
+  fn declared_expression(source: &str) -> Expression {
+    let mut program = parse_source(&format!("Dim x = {}", source), "<test>").unwrap();
+    match program.statements.remove(0) {
+      Statement::Declaration { expression, .. } => expression,
+      s => panic!("expected a declaration, got {:?}", s),
+    }
+  }
+
+  #[test]
+  fn negative_literals_fold_but_subtraction_stays_binary() {
+    assert_eq!(declared_expression("-5"), Expression::Integer(-5));
+    assert_eq!(
+      declared_expression("0 - 5"),
+      Expression::Binary {
+        left: Box::new(Expression::Integer(0)),
+        operator: BinaryOperator::SUB,
+        right: Box::new(Expression::Integer(5)),
+      }
+    );
+    assert_eq!(
+      declared_expression("-a"),
+      Expression::Unary {
+        operator: UnaryOperator::NEGATIVE,
+        expression: Box::new(Expression::Identifier("a".to_string())),
+      }
+    );
+    assert!(matches!(
+      declared_expression("-2 ^ 2"),
+      Expression::Unary { .. }
+    ));
+    assert_eq!(eval_str("Dim x = 0 - 5"), eval_str("Dim x = -5"));
+  }
+
+  fn value(source: &str) -> Object {
+    eval_str(&format!("Dim x = {}", source)).unwrap()
+  }
+
+  #[test]
+  fn multiplication_binds_tighter_than_addition() {
+    assert_eq!(value("2 + 3 * 4"), Object::Integer(14));
+    assert_eq!(value("2 * 3 + 4"), Object::Integer(10));
+    assert_eq!(value("10 - 2 * 3"), Object::Integer(4));
+    assert_eq!(value("(2 + 3) * 4"), Object::Integer(20));
+    assert_eq!(value("1 + 10 % 4 * 2"), Object::Integer(5));
+    assert_eq!(
+      declared_expression("2 + 3 * 4"),
+      Expression::Binary {
+        left: Box::new(Expression::Integer(2)),
+        operator: BinaryOperator::ADD,
+        right: Box::new(Expression::Binary {
+          left: Box::new(Expression::Integer(3)),
+          operator: BinaryOperator::MUL,
+          right: Box::new(Expression::Integer(4)),
+        }),
+      }
+    );
+  }
+
+  #[test]
+  fn operators_of_equal_precedence_fold_from_the_left() {
+    assert_eq!(value("10 - 3 - 2"), Object::Integer(5));
+    assert_eq!(value("100 / 5 / 2"), Object::Integer(10));
+    assert_eq!(value("100 % 7 % 3"), Object::Integer(2));
+    assert_eq!(value("10 - 3 + 2"), Object::Integer(9));
+    assert_eq!(value("8 / 4 * 2"), Object::Integer(4));
+  }
+
+  fn parse_with_alias(source: &str, alias: &str, kind: TokenKind) -> Result<Program, ParseError> {
+    let mut parser = Parser::new(Lexer::new(InputStream::new(source, "<test>".to_string())))?;
+    parser.set_operator_alias(alias, kind);
+    parser.parse_program()
+  }
+
+  #[test]
+  fn an_alias_makes_a_word_an_operator() {
+    let program = parse_with_alias("Dim x = 7 mod 3", "mod", TokenKind::PERCENT).unwrap();
+    assert_eq!(program, parse_source("Dim x = 7 % 3", "<test>").unwrap());
+    assert_eq!(Executor::new().execute(&program), Ok(Object::Integer(1)));
+    assert!(parse_with_alias("mod = 1", "mod", TokenKind::PERCENT).is_err());
+    assert!(parse_source("Dim x = 7 mod 3", "<test>").is_err());
+  }
+
+  #[test]
+  fn comparisons_parse_below_arithmetic() {
+    for (symbol, operator) in [
+      ("==", BinaryOperator::EQ),
+      ("!=", BinaryOperator::NE),
+      ("<", BinaryOperator::LT),
+      (">", BinaryOperator::GT),
+      ("<=", BinaryOperator::LE),
+      (">=", BinaryOperator::GE),
+    ] {
+      assert_eq!(
+        declared_expression(&format!("1 + 2 {} 3", symbol)),
+        Expression::Binary {
+          left: Box::new(Expression::Binary {
+            left: Box::new(Expression::Integer(1)),
+            operator: BinaryOperator::ADD,
+            right: Box::new(Expression::Integer(2)),
+          }),
+          operator,
+          right: Box::new(Expression::Integer(3)),
+        }
+      );
+    }
+  }
+
+  #[test]
+  fn integer_literals_that_do_not_fit_are_parse_errors() {
+    assert_eq!(
+      error_type("Dim x = 9999999999"),
+      ParseErrorType::InvalidIntegerLiteral {
+        literal: "9999999999".to_string()
+      }
+    );
+    assert_eq!(
+      parse_with_alias("Dim x = huge", "huge", TokenKind::INT)
+        .unwrap_err()
+        .error_type,
+      ParseErrorType::InvalidIntegerLiteral {
+        literal: "huge".to_string()
+      }
+    );
+  }
+
+  #[test]
+  fn prefix_signs_bind_tighter_than_multiplication() {
+    assert_eq!(value("-3 * 2"), Object::Integer(-6));
+    assert_eq!(value("-(3 + 2)"), Object::Integer(-5));
+    assert_eq!(value("- -5"), Object::Integer(5));
+    assert_eq!(value("+4 - -1"), Object::Integer(5));
+    assert_eq!(
+      declared_expression("-(3 + 2)"),
+      Expression::Unary {
+        operator: UnaryOperator::NEGATIVE,
+        expression: Box::new(Expression::Binary {
+          left: Box::new(Expression::Integer(3)),
+          operator: BinaryOperator::ADD,
+          right: Box::new(Expression::Integer(2)),
+        }),
+      }
+    );
+    assert_eq!(declared_expression("- -5"), Expression::Integer(5));
+  }
+
+  #[test]
+  fn a_guard_else_block_must_exit() {
+    assert_eq!(
+      error_type("Guard true Else\n  Dim x = 1\nEnd Guard"),
+      ParseErrorType::GuardDoesNotExit
+    );
+    assert_eq!(
+      error_type(
+        "Do\n  Guard true Else\n    If false Then\n      Exit Do\n    End If\n  End Guard\nLoop"
+      ),
+      ParseErrorType::GuardDoesNotExit
+    );
+    assert!(parse_source(
+      "Do\n  Guard true Else\n    If false Then\n      Exit Do\n    Else\n      Halt(1)\n    End If\n  End Guard\nLoop",
+      "<test>"
+    )
+    .is_ok());
+  }
+
+  #[test]
+  fn arguments_are_separated_by_commas() {
+    let program = parse_source("Print(1, 2, 3)", "<test>").unwrap();
+    assert_eq!(
+      program.statements,
+      vec![Statement::MethodInvocation {
+        identifier: "Print".to_string(),
+        arguments: vec![
+          Expression::Integer(1),
+          Expression::Integer(2),
+          Expression::Integer(3),
+        ],
+      }]
+    );
+  }
+
+  #[test]
+  fn a_trailing_comma_is_a_missing_expression() {
+    for source in ["Print(1,)", "Dim a, b = 1,"] {
+      let error = parse_source(source, "<test>").unwrap_err();
+      assert_eq!(
+        error.error_type,
+        ParseErrorType::ExpectedExpression {
+          after: TokenKind::COMMA
+        },
+        "{}",
+        source
+      );
+      assert!(error
+        .error_message
+        .starts_with("Expected expression after ','"));
+    }
+  }
+
+  #[test]
+  fn parse_errors_point_at_the_offending_token() {
+    let error = parse_source("Dim a = 1\r\nDim b = 2\r\nPrint(a, ))", "<test>").unwrap_err();
+    assert_eq!((error.line, error.column), (2, 9));
+    assert!(error.to_string().contains("line: 3, column: 10, at <test>"));
+  }
+
+  #[test]
+  fn a_comparison_declaration_round_trips() {
+    for (symbol, operator) in [
+      ("==", BinaryOperator::EQ),
+      ("!=", BinaryOperator::NE),
+      ("<", BinaryOperator::LT),
+      (">", BinaryOperator::GT),
+      ("<=", BinaryOperator::LE),
+      (">=", BinaryOperator::GE),
+    ] {
+      let source = format!("Const b = 3 {} 5", symbol);
+      let program = parse_source(&source, "<test>").unwrap();
+      assert_eq!(
+        program.statements,
+        vec![Statement::Declaration {
+          identifier: "b".to_string(),
+          expression: Expression::Binary {
+            left: Box::new(Expression::Integer(3)),
+            operator,
+            right: Box::new(Expression::Integer(5)),
+          },
+          constant: true,
+        }]
+      );
+      let printed = crate::ast::source::to_source(&program);
+      assert_eq!(parse_source(&printed, "<test>").unwrap(), program);
+    }
+  }
+
+  #[test]
+  fn collecting_keeps_good_lines_around_bad_ones() {
+    let (program, errors) = parse_source_collecting(
+      "Dim a = 1\nDim = 2\nDim b = 3\nDim c = (4\nDim d = 5",
+      "<test>",
+    );
+    let names: Vec<_> = program
+      .statements
+      .iter()
+      .map(|s| match s {
+        Statement::Declaration { identifier, .. } => identifier.as_str(),
+        other => panic!("unexpected statement {:?}", other),
+      })
+      .collect();
+    assert_eq!(names, vec!["a", "b", "d"]);
+    let lines: Vec<_> = errors.iter().map(|e| e.line).collect();
+    assert_eq!(lines, vec![1, 3]);
+  }
+
+  #[test]
+  fn collecting_reports_one_error_per_line() {
+    let (_, errors) = parse_source_collecting("Dim = = =\nPrint(1, , )", "<test>");
+    let lines: Vec<_> = errors.iter().map(|e| e.line).collect();
+    assert_eq!(lines, vec![0, 1]);
+  }
+
+  #[test]
+  fn collecting_includes_lexer_errors() {
+    let (program, errors) = parse_source_collecting("Dim a = 1\nDim b = 2 @\nDim c = 3", "<test>");
+    assert_eq!(program.statements.len(), 2);
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].line, 1);
+    assert_eq!(
+      errors[0].error_type,
+      ParseErrorType::UnexpectedCharacter { ch: '@' }
+    );
+  }
+
+  #[test]
+  fn a_guard_may_halt_in_any_case() {
+    for halt in ["Halt", "HALT", "halt"] {
+      let source = format!("Guard false Else\n  {}(1)\nEnd Guard", halt);
+      assert!(parse_source(&source, "<test>").is_ok());
+    }
+    let mut executor = Executor::new();
+    executor.set_case_insensitive(true);
+    let program = parse_source("Guard false Else\n  HALT(3)\nEnd Guard", "<test>").unwrap();
+    assert_eq!(executor.execute(&program), Err(RuntimeError::Halt(3)));
   }
 }
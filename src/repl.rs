@@ -0,0 +1,98 @@
+use super::eval::EvalError;
+use super::executor::Executor;
+use super::object::Object;
+use super::parser;
+use std::io::{self, BufRead, Write};
+
+// Evaluates one line at a time on a single Executor, so variables declared on
+// one line stay visible on the next.
+pub struct Repl {
+  executor: Executor,
+}
+
+impl Default for Repl {
+  fn default() -> Repl {
+    Repl::new()
+  }
+}
+
+impl Repl {
+  pub fn new() -> Repl {
+    Repl {
+      executor: Executor::new(),
+    }
+  }
+
+  pub fn executor_mut(&mut self) -> &mut Executor {
+    &mut self.executor
+  }
+
+  // Returns None for blank lines and for statements without a value.
+  pub fn eval_line(&mut self, line: &str) -> Result<Option<Object>, EvalError> {
+    if line.trim().is_empty() {
+      return Ok(None);
+    }
+    let program = parser::parse_source(line, "<repl>").map_err(EvalError::Parse)?;
+    match self.executor.execute(&program) {
+      Ok(Object::Undefined) | Ok(Object::Unit) => Ok(None),
+      Ok(value) => Ok(Some(value)),
+      Err(e) => Err(EvalError::Runtime(e)),
+    }
+  }
+
+  // Reads lines until the input ends. Errors are reported on `error` and the
+  // session goes on with the next line.
+  pub fn run<R: BufRead, W: Write, E: Write>(
+    &mut self,
+    input: R,
+    mut output: W,
+    mut error: E,
+  ) -> io::Result<()> {
+    write!(output, "> ")?;
+    output.flush()?;
+    for line in input.lines() {
+      match self.eval_line(&line?) {
+        Ok(Some(value)) => writeln!(output, "{}", value)?,
+        Ok(None) => {}
+        Err(e) => writeln!(error, "{}", e)?,
+      }
+      write!(output, "> ")?;
+      output.flush()?;
+    }
+    writeln!(output)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn variables_live_across_lines() {
+    let mut repl = Repl::new();
+    assert_eq!(repl.eval_line("Const x = 5"), Ok(Some(Object::Integer(5))));
+    assert_eq!(repl.eval_line("   "), Ok(None));
+    assert_eq!(
+      repl.eval_line("Dim y = x + 1"),
+      Ok(Some(Object::Integer(6)))
+    );
+    assert_eq!(repl.eval_line("AssertEq(y, 6)"), Ok(None));
+  }
+
+  #[test]
+  fn a_bad_line_does_not_end_the_session() {
+    let mut repl = Repl::new();
+    repl.executor_mut().set_output(Box::new(io::sink()));
+    let input = "Dim x = 5\nDim = 1\nDim z = 1 / 0\n\nDim y = x * 2\n";
+    let (mut output, mut error) = (vec![], vec![]);
+    repl.run(input.as_bytes(), &mut output, &mut error).unwrap();
+    assert_eq!(
+      String::from_utf8(output).unwrap(),
+      "> Integer(5)\n> > > > Integer(10)\n> \n"
+    );
+    let error = String::from_utf8(error).unwrap();
+    assert!(error.starts_with("Compile error"));
+    assert_eq!(error.matches("Compile error").count(), 1);
+    assert_eq!(error.matches("Execution error").count(), 1);
+  }
+}
@@ -1,4 +1,5 @@
-use super::object::RuntimeType;
+use super::ast::BinaryOperator;
+use super::object::{Object, RuntimeType};
 use std::fmt;
 
 #[derive(Debug, PartialEq)]
@@ -10,6 +11,45 @@ pub enum RuntimeError {
     expected: RuntimeType,
     actual: RuntimeType,
   },
+  NotOrderable(RuntimeType),
+  WrongNumberOfArguments {
+    method: String,
+    expected: usize,
+    actual: usize,
+  },
+  InvalidArgument(String),
+  InvalidConversion(String),
+  InvalidRegex(String),
+  InvalidIndexType(RuntimeType),
+  IndexOutOfRange {
+    index: i32,
+    length: usize,
+  },
+  OutputError(String),
+  MemoryLimitExceeded {
+    limit: usize,
+    used: usize,
+  },
+  IntegerOverflow {
+    operator: BinaryOperator,
+  },
+  FloatingPointError {
+    operator: BinaryOperator,
+  },
+  LoopLimitExceeded(usize),
+  AssertionFailed {
+    actual: Object,
+    expected: Object,
+  },
+  Halt(i32),
+  NotHashable(RuntimeType),
+  DivisionByZero,
+  AssignToConst(String),
+  AlreadyDeclared(String),
+  UnpackMismatch {
+    expected: usize,
+    actual: usize,
+  },
 }
 
 impl fmt::Display for RuntimeError {
@@ -27,6 +67,83 @@ impl fmt::Display for RuntimeError {
         "Type mismatch, expected type is {}, but actual is {}.",
         expected, actual
       ),
+      RuntimeError::NotOrderable(t) => {
+        write!(f, "NotOrderable, {} values cannot be ordered.", t)
+      }
+      RuntimeError::WrongNumberOfArguments {
+        method,
+        expected,
+        actual,
+      } => write!(
+        f,
+        "WrongNumberOfArguments, {} expects {} argument(s), but {} given.",
+        method, expected, actual
+      ),
+      RuntimeError::InvalidArgument(message) => write!(f, "InvalidArgument, {}", message),
+      RuntimeError::InvalidConversion(message) => write!(f, "InvalidConversion, {}", message),
+      RuntimeError::InvalidRegex(message) => write!(f, "InvalidRegex, {}", message),
+      RuntimeError::InvalidIndexType(t) => {
+        write!(
+          f,
+          "InvalidIndexType, {} values cannot be used as an index.",
+          t
+        )
+      }
+      RuntimeError::IndexOutOfRange { index, length } => write!(
+        f,
+        "IndexOutOfRange, index {} is out of range for length {}.",
+        index, length
+      ),
+      RuntimeError::OutputError(message) => write!(f, "OutputError, {}", message),
+      RuntimeError::MemoryLimitExceeded { limit, used } => write!(
+        f,
+        "MemoryLimitExceeded, {} bytes used, but the limit is {} bytes.",
+        used, limit
+      ),
+      RuntimeError::IntegerOverflow { operator } => {
+        write!(f, "IntegerOverflow, {} overflowed an Integer.", operator)
+      }
+      RuntimeError::FloatingPointError { operator } => write!(
+        f,
+        "FloatingPointError, {} gave a NaN or infinite Float.",
+        operator
+      ),
+      RuntimeError::LoopLimitExceeded(limit) => {
+        write!(
+          f,
+          "LoopLimitExceeded, a loop ran more than {} times.",
+          limit
+        )
+      }
+      RuntimeError::AssertionFailed { actual, expected } => write!(
+        f,
+        "AssertionFailed, expected {}, but actual is {}.",
+        expected, actual
+      ),
+      RuntimeError::Halt(code) => write!(f, "Halt, stopped with code {}.", code),
+      RuntimeError::DivisionByZero => write!(f, "DivisionByZero, cannot divide by zero."),
+      RuntimeError::AssignToConst(name) => {
+        write!(
+          f,
+          "AssignToConst, {} is a constant and cannot be changed.",
+          name
+        )
+      }
+      RuntimeError::AlreadyDeclared(name) => {
+        write!(
+          f,
+          "AlreadyDeclared, {} is already declared in this scope.",
+          name
+        )
+      }
+      RuntimeError::UnpackMismatch { expected, actual } => write!(
+        f,
+        "UnpackMismatch, expected {} values to assign, but {} given.",
+        expected, actual
+      ),
+      RuntimeError::NotHashable(t) => {
+        write!(f, "NotHashable, {} values cannot be stored in a Set.", t)
+      }
     }
   }
 }
@@ -0,0 +1,68 @@
+#[cfg(not(feature = "wasm"))]
+use std::convert::TryFrom;
+#[cfg(not(feature = "wasm"))]
+use std::thread;
+use std::time::Duration;
+#[cfg(not(feature = "wasm"))]
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+pub trait Timer {
+  fn sleep(&mut self, duration: Duration);
+}
+
+#[cfg(not(feature = "wasm"))]
+pub struct ThreadTimer;
+
+#[cfg(not(feature = "wasm"))]
+impl Timer for ThreadTimer {
+  fn sleep(&mut self, duration: Duration) {
+    thread::sleep(duration);
+  }
+}
+
+// Time elapsed since an arbitrary, fixed starting point.
+pub trait Clock {
+  fn now(&mut self) -> Duration;
+
+  // Seconds since the Unix epoch, for builtins such as FormatTime that need
+  // the date. None for clocks that only measure elapsed time.
+  fn unix_seconds(&mut self) -> Option<i64> {
+    None
+  }
+}
+
+// Reads the system time once, when created, and advances it with Instant.
+#[cfg(not(feature = "wasm"))]
+pub struct InstantClock {
+  started: Instant,
+  started_unix: Option<Duration>,
+}
+
+#[cfg(not(feature = "wasm"))]
+impl InstantClock {
+  pub fn new() -> InstantClock {
+    InstantClock {
+      started: Instant::now(),
+      started_unix: SystemTime::now().duration_since(UNIX_EPOCH).ok(),
+    }
+  }
+}
+
+#[cfg(not(feature = "wasm"))]
+impl Default for InstantClock {
+  fn default() -> InstantClock {
+    InstantClock::new()
+  }
+}
+
+#[cfg(not(feature = "wasm"))]
+impl Clock for InstantClock {
+  fn now(&mut self) -> Duration {
+    self.started.elapsed()
+  }
+
+  fn unix_seconds(&mut self) -> Option<i64> {
+    let unix = self.started_unix? + self.started.elapsed();
+    i64::try_from(unix.as_secs()).ok()
+  }
+}
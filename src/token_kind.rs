@@ -8,10 +8,14 @@ pub enum TokenKind {
   COMMA,
   IDENT,
   INT,
+  FLOAT,
   STRING,
   CONST,
   LPAREN,
   RPAREN,
+  LBRACKET,
+  RBRACKET,
+  ELLIPSIS,
   PLUS,
   MINUS,
   ASTERISK,
@@ -30,9 +34,16 @@ pub enum TokenKind {
   END,
   FOR,
   NEXT,
+  DO,
+  LOOP,
+  WHILE,
+  EXIT,
+  TRUE,
+  FALSE,
+  GUARD,
   DIM,
   TO,
-  // EQ,
+  EQ,
   NE,
   GT,
   GE,
@@ -40,6 +51,32 @@ pub enum TokenKind {
   LE,
 }
 
+impl TokenKind {
+  pub fn is_operator(&self) -> bool {
+    matches!(
+      self,
+      TokenKind::PLUS
+        | TokenKind::MINUS
+        | TokenKind::ASTERISK
+        | TokenKind::SLASH
+        | TokenKind::ASSIGN
+        | TokenKind::PERCENT
+        | TokenKind::HAT
+        | TokenKind::MOD
+        | TokenKind::AND
+        | TokenKind::OR
+        | TokenKind::XOR
+        | TokenKind::NOT
+        | TokenKind::EQ
+        | TokenKind::NE
+        | TokenKind::GT
+        | TokenKind::GE
+        | TokenKind::LT
+        | TokenKind::LE
+    )
+  }
+}
+
 impl fmt::Display for TokenKind {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
     write!(f, "{:?}", self)
@@ -0,0 +1,59 @@
+use bsharp_lang::executor::Executor;
+use bsharp_lang::input_stream::InputStream;
+use bsharp_lang::lexer::Lexer;
+use bsharp_lang::object::Object;
+use bsharp_lang::parser::Parser;
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+use std::rc::Rc;
+
+#[derive(Clone, Default)]
+struct Output(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Output {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    self.0.borrow_mut().extend_from_slice(buf);
+    Ok(buf.len())
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    Ok(())
+  }
+}
+
+#[test]
+fn a_source_string_is_lexed_parsed_and_run() {
+  let lexer = Lexer::new(InputStream::new(
+    "Const x = 2 + 3\nPrint(x)",
+    String::from("<test>"),
+  ));
+  let program = Parser::new(lexer).unwrap().parse_program().unwrap();
+  let output = Output::default();
+  let mut executor = Executor::new();
+  executor.set_output(Box::new(output.clone()));
+  assert_eq!(executor.execute(&program), Ok(Object::Unit));
+  assert_eq!(executor.get_variable("x"), Some(Object::Integer(5)));
+  assert_eq!(*output.0.borrow(), b"Integer(5)\n");
+}
+
+#[test]
+fn the_repl_prints_only_prompts_and_values() {
+  let mut child = Command::new(env!("CARGO_BIN_EXE_bsharp-lang"))
+    .stdin(Stdio::piped())
+    .stdout(Stdio::piped())
+    .stderr(Stdio::null())
+    .spawn()
+    .unwrap();
+  child
+    .stdin
+    .take()
+    .unwrap()
+    .write_all(b"Dim x = 5\nPrint(x)\n")
+    .unwrap();
+  let output = child.wait_with_output().unwrap();
+  assert_eq!(
+    String::from_utf8(output.stdout).unwrap(),
+    "> Integer(5)\n> Integer(5)\n> \n"
+  );
+}